@@ -0,0 +1,45 @@
+//! Colors the UIs and the server's renderer draw states with.
+
+/// The color a state is drawn with: the state itself when it is a hex color
+/// like `#ff0000`, otherwise a stable color derived from its text.
+pub fn state_rgb(state: &str) -> [u8; 3] {
+    let hex = state.trim_matches(['#', '"', '[', ']']).to_lowercase();
+
+    // Non-ASCII text may put a char boundary inside a channel, so channels
+    // are only taken when the slice is valid.
+    let channel = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+
+    if let (Some(r), Some(g), Some(b)) = (channel(0..2), channel(2..4), channel(4..6)) {
+        return [r, g, b];
+    }
+
+    let digest = md5::compute(state.as_bytes());
+
+    [digest[0], digest[1], digest[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_rgb_reads_hex_colors() {
+        assert_eq!(state_rgb("#ff8000"), [0xff, 0x80, 0x00]);
+        assert_eq!(state_rgb("\"[#00FF7f]\""), [0x00, 0xff, 0x7f]);
+    }
+
+    #[test]
+    fn state_rgb_hashes_other_text() {
+        assert_eq!(state_rgb("alive"), state_rgb("alive"));
+        assert_ne!(state_rgb("alive"), state_rgb("dead"));
+    }
+
+    #[test]
+    fn state_rgb_does_not_panic_on_non_ascii() {
+        for state in ["aé1234", "é", "#ééé", "ff00é0", "日本語テキスト"] {
+            let digest = md5::compute(state.as_bytes());
+
+            assert_eq!(state_rgb(state), [digest[0], digest[1], digest[2]]);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Types of the LLMCA server API and, with the default `http` feature, an
+//! async client for it. UIs that only talk to a remote simulation depend on
+//! this crate instead of `dynamical-system`.
+
+mod color;
+mod types;
+
+pub use color::state_rgb;
+pub use types::*;
+
+#[cfg(feature = "http")]
+pub use http::{Client, ClientError};
+
+#[cfg(feature = "http")]
+mod http {
+    use std::fmt;
+
+    use reqwest::{RequestBuilder, StatusCode};
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use crate::types::*;
+
+    /// Header the server reads API keys from.
+    pub const API_KEY_HEADER: &str = "X-API-Key";
+
+    #[derive(Debug)]
+    pub enum ClientError {
+        /// The request could not be sent or the response not decoded.
+        Http(reqwest::Error),
+        /// The server answered with an error status.
+        Api { status: StatusCode, error: String },
+    }
+
+    impl fmt::Display for ClientError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ClientError::Http(err) => write!(f, "{err}"),
+                ClientError::Api { status, error } => write!(f, "{status}: {error}"),
+            }
+        }
+    }
+
+    impl std::error::Error for ClientError {}
+
+    impl From<reqwest::Error> for ClientError {
+        fn from(err: reqwest::Error) -> Self {
+            ClientError::Http(err)
+        }
+    }
+
+    pub type Result<T> = std::result::Result<T, ClientError>;
+
+    /// Talks to a server at `base_url`, e.g. `http://localhost:8000/api`.
+    #[derive(Debug, Clone)]
+    pub struct Client {
+        http: reqwest::Client,
+        base_url: String,
+        api_key: Option<String>,
+    }
+
+    impl Client {
+        pub fn new(base_url: impl Into<String>) -> Self {
+            Self {
+                http: reqwest::Client::new(),
+                base_url: base_url.into().trim_end_matches('/').to_string(),
+                api_key: None,
+            }
+        }
+
+        pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+            self.api_key = Some(api_key.into());
+            self
+        }
+
+        pub fn base_url(&self) -> &str {
+            &self.base_url
+        }
+
+        /// Entities the caller can access.
+        pub async fn entities(&self) -> Result<EntityList> {
+            self.get("/life").await
+        }
+
+        pub async fn create_entity(&self, payload: &CreateEntityPayload) -> Result<CreatedEntity> {
+            self.post("/entities", payload).await
+        }
+
+        pub async fn summary(&self, id: &str) -> Result<EntitySummary> {
+            self.get(&format!("/entities/{id}")).await
+        }
+
+        pub async fn state(&self, id: &str) -> Result<EntityStateView> {
+            self.get(&format!("/entity/{id}/state")).await
+        }
+
+        pub async fn step(&self, id: &str, step: u32) -> Result<StepGrid> {
+            self.get(&format!("/entities/{id}/steps/{step}")).await
+        }
+
+        /// Step `step` rendered as a PNG.
+        pub async fn frame_png(
+            &self,
+            id: &str,
+            step: u32,
+            cell_size: Option<u32>,
+        ) -> Result<Vec<u8>> {
+            let query = query(&[("cell_size", cell_size.map(|size| size as usize))]);
+            let request = self.request(
+                reqwest::Method::GET,
+                &format!("/entities/{id}/steps/{step}/frame.png{query}"),
+            );
+
+            let response = check(request.send().await?).await?;
+
+            Ok(response.bytes().await?.to_vec())
+        }
+
+        /// Computes the next step and waits for it.
+        pub async fn evolve(&self, id: &str) -> Result<StepResult> {
+            self.post(&format!("/entity/{id}/evolve"), &()).await
+        }
+
+        pub async fn interact(
+            &self,
+            id: &str,
+            payload: &InteractionPayload,
+        ) -> Result<InterventionResult> {
+            self.post(&format!("/entity/{id}/interact"), payload).await
+        }
+
+        pub async fn rewind(&self, id: &str, step: u32) -> Result<RewindResult> {
+            self.post(&format!("/entity/{id}/rewind"), &RewindPayload { step })
+                .await
+        }
+
+        pub async fn unit_history(&self, id: &str, x: usize, y: usize) -> Result<UnitHistory> {
+            self.get(&format!("/entities/{id}/units/{x}/{y}/history"))
+                .await
+        }
+
+        pub async fn telemetry(
+            &self,
+            id: &str,
+            offset: Option<usize>,
+            limit: Option<usize>,
+        ) -> Result<TelemetryPage> {
+            let query = query(&[("offset", offset), ("limit", limit)]);
+
+            self.get(&format!("/entities/{id}/telemetry{query}")).await
+        }
+
+        /// Starts a background run; poll it with [`Client::run`].
+        pub async fn start_run(&self, id: &str, payload: &RunPayload) -> Result<RunView> {
+            self.post(&format!("/entities/{id}/runs"), payload).await
+        }
+
+        pub async fn runs(&self, id: &str) -> Result<RunList> {
+            self.get(&format!("/entities/{id}/runs")).await
+        }
+
+        pub async fn run(&self, run: &str) -> Result<RunView> {
+            self.get(&format!("/runs/{run}")).await
+        }
+
+        pub async fn pause(&self, run: &str) -> Result<RunView> {
+            self.post(&format!("/runs/{run}/pause"), &()).await
+        }
+
+        pub async fn resume(&self, run: &str) -> Result<RunView> {
+            self.post(&format!("/runs/{run}/resume"), &()).await
+        }
+
+        pub async fn cancel(&self, run: &str) -> Result<RunView> {
+            self.post(&format!("/runs/{run}/cancel"), &()).await
+        }
+
+        pub async fn me(&self) -> Result<Me> {
+            self.get("/me").await
+        }
+
+        pub async fn resolvers(&self) -> Result<Vec<ResolverView>> {
+            self.get("/resolvers").await
+        }
+
+        /// Admins only.
+        pub async fn resolvers_health(&self) -> Result<Vec<ResolverHealthView>> {
+            self.get("/resolvers/health").await
+        }
+
+        async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+            json(self.request(reqwest::Method::GET, path)).await
+        }
+
+        async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+            &self,
+            path: &str,
+            body: &B,
+        ) -> Result<T> {
+            json(self.request(reqwest::Method::POST, path).json(body)).await
+        }
+
+        fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+            let request = self
+                .http
+                .request(method, format!("{}{path}", self.base_url));
+
+            match &self.api_key {
+                Some(api_key) => request.header(API_KEY_HEADER, api_key),
+                None => request,
+            }
+        }
+    }
+
+    /// `?name=value&...` for the parameters that are set, or nothing.
+    fn query(params: &[(&str, Option<usize>)]) -> String {
+        let params = params
+            .iter()
+            .filter_map(|(name, value)| value.map(|value| format!("{name}={value}")))
+            .collect::<Vec<_>>();
+
+        match params.is_empty() {
+            true => String::new(),
+            false => format!("?{}", params.join("&")),
+        }
+    }
+
+    async fn json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+        let response = check(request.send().await?).await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Turns an error status into [`ClientError::Api`], with the message of
+    /// the server's JSON error body when there is one.
+    async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+
+        let error = serde_json::from_str::<ErrorBody>(&body)
+            .map(|body| body.error)
+            .unwrap_or(body);
+
+        Err(ClientError::Api { status, error })
+    }
+}
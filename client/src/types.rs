@@ -0,0 +1,802 @@
+//! Request and response bodies of the server API. With the `openapi` feature
+//! they also derive the `poem-openapi` traits the server describes them with.
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "openapi")]
+use poem_openapi::{Enum, Object, Union};
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+impl ErrorBody {
+    pub fn new(error: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+        }
+    }
+}
+
+/// Why a request conflicts with the entity's current activity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ConflictBody {
+    pub error: String,
+    /// The step the entity is computing, if that is the conflict.
+    pub computing_step: Option<u32>,
+    /// The entity's active run, if that is the conflict.
+    pub run: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+}
+
+// Entities
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct InitialStatePayload {
+    pub rule: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Enum), oai(rename_all = "snake_case"))]
+pub enum NeighborhoodPayload {
+    Moore,
+    VonNeumann,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct TopologyPayload {
+    pub neighborhood: Option<NeighborhoodPayload>,
+    pub wrap: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct CreateEntityPayload {
+    pub width: usize,
+    pub height: usize,
+    pub memory_size: usize,
+    /// Assigned to cells cyclically, in lattice order.
+    pub initial_states: Vec<InitialStatePayload>,
+    pub topology: Option<TopologyPayload>,
+    /// Resolver ids (`name`, or `model_name` when unnamed); all when omitted.
+    pub resolvers: Option<Vec<String>>,
+    /// Round-robin when omitted.
+    pub assignment: Option<AssignmentPayload>,
+    /// Gives each unit a resolver of its own.
+    pub species: Option<SpeciesPayload>,
+    /// Whether answers may be reused from the response cache; false when
+    /// omitted.
+    pub cache_responses: Option<bool>,
+}
+
+/// Which resolver each unit belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct SpeciesPayload {
+    /// Resolver ids, from the entity's selection if it has one.
+    pub resolvers: Vec<String>,
+    /// `bands` when omitted.
+    pub layout: Option<SpeciesLayoutPayload>,
+    /// Whether a unit that adopts a neighbor's rule also takes its species.
+    pub inherit: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Enum), oai(rename_all = "snake_case"))]
+pub enum SpeciesLayoutPayload {
+    /// Equal contiguous bands, in lattice order.
+    Bands,
+    /// Cyclically, in lattice order.
+    Cyclic,
+}
+
+/// How the units of a step are spread over the entity's resolvers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Enum), oai(rename_all = "snake_case"))]
+pub enum AssignmentPayload {
+    RoundRobin,
+    /// In proportion to the resolvers' capacity.
+    Weighted,
+    /// The same resolver for a unit every step.
+    Sticky,
+    /// One contiguous band of the lattice per resolver.
+    Region,
+    /// Weighted by capacity and observed throughput.
+    Adaptive,
+}
+
+/// The configuration an entity was created with, as saved in `entity.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ManifestView {
+    pub id: String,
+    /// RFC 3339.
+    pub created_at: String,
+    pub width: usize,
+    pub height: usize,
+    pub memory_size: usize,
+    pub initial_states: Vec<InitialStatePayload>,
+    pub topology: TopologyPayload,
+    pub resolvers: Option<Vec<String>>,
+    pub assignment: AssignmentPayload,
+    pub species: Option<SpeciesPayload>,
+    pub cache_responses: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct CreatedEntity {
+    pub entity: String,
+    /// Unset while authentication is disabled.
+    pub owner: Option<String>,
+    pub step: u32,
+    pub manifest: Option<ManifestView>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ImportedEntity {
+    pub entity: String,
+    pub step: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct RegisteredEntity {
+    /// Folder the entity was registered in.
+    pub entity: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct EntityList {
+    pub entities: Vec<String>,
+}
+
+/// The whole entity: its summary, how it was created and every unit with
+/// its memory at the current step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct EntityDump {
+    pub entity: EntitySummary,
+    /// Missing for entities created before manifests were saved.
+    pub manifest: Option<ManifestView>,
+    /// In lattice order.
+    pub units: Vec<UnitDump>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct UnitDump {
+    pub x: usize,
+    pub y: usize,
+    pub species: Option<String>,
+    pub memory_size: usize,
+    /// Oldest first.
+    pub memory: Vec<MemoryEntryView>,
+}
+
+/// One entry of a unit's memory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct MemoryEntryView {
+    /// RFC 3339.
+    pub timestamp: String,
+    pub rule: String,
+    pub state: String,
+    pub feedback: String,
+    pub outcome: UnitOutcomeView,
+    pub tokens: u64,
+    pub resolver_id: Option<String>,
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Enum), oai(rename_all = "snake_case"))]
+pub enum EntityActivity {
+    Idle,
+    /// Computing `computing_step`.
+    Computing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct EntityStateView {
+    pub step: u32,
+    pub state: EntityActivity,
+    pub computing_step: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct EntitySummary {
+    pub id: String,
+    pub step: u32,
+    pub state: EntityActivity,
+    pub computing_step: Option<u32>,
+    pub saved_steps: Vec<u32>,
+    /// Everything spent computing steps so far, including rewound ones.
+    pub usage: EntityUsageView,
+}
+
+/// Tokens and cost spent by one resolver.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ResolverUsageView {
+    pub units: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    /// In the currency of the resolver's price table; 0 when it has none.
+    pub cost: f64,
+}
+
+/// Cumulative tokens and cost of an entity, per resolver too.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct EntityUsageView {
+    pub steps: u32,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    pub resolvers: BTreeMap<String, ResolverUsageView>,
+}
+
+// History
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct TelemetryView {
+    pub units_total: usize,
+    pub units_completed: usize,
+    pub resolver_count: usize,
+    pub chunks: usize,
+    /// Transport errors and timeouts.
+    pub llm_failures: usize,
+    /// Schema violations and rejected answers.
+    pub parse_failures: usize,
+    /// Completed units by outcome, keyed like [`UnitOutcomeView`].
+    pub outcomes: BTreeMap<String, usize>,
+    /// Units computed after failing on another resolver.
+    pub failovers: usize,
+    pub hedged: usize,
+    /// Hedged units the second resolver answered first.
+    pub hedge_wins: usize,
+    /// Units per species after the step.
+    pub species: BTreeMap<String, usize>,
+    /// Units that took on a neighbor's species along with its rule.
+    pub species_adoptions: usize,
+    /// Units answered from the response cache and lookups that missed.
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+    pub cache_hit_rate: f64,
+    pub unique_states: usize,
+    pub total_tokens: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost: f64,
+    /// Units, tokens and cost per resolver that computed part of the step.
+    pub resolvers: BTreeMap<String, ResolverUsageView>,
+    /// Call counts, failure classes and latency percentiles per resolver.
+    pub resolver_stats: BTreeMap<String, ResolverStatsView>,
+    pub elapsed_ms: u64,
+}
+
+/// How one resolver behaved during a step. Latencies cover failed calls too.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ResolverStatsView {
+    pub calls: usize,
+    /// Calls that yielded a valid unit, retried ones included.
+    pub successes: usize,
+    pub retried_ok: usize,
+    pub transport_failures: usize,
+    pub timeouts: usize,
+    pub structured_output_failures: usize,
+    pub validation_rejections: usize,
+    /// Share of calls that returned a valid unit, from 0 to 1.
+    pub success_rate: f64,
+    pub latency_p50_ms: u64,
+    pub latency_p95_ms: u64,
+    pub latency_p99_ms: u64,
+    pub latency_max_ms: u64,
+}
+
+/// How the computation of a unit ended. Initial states and manual edits are
+/// `ok`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Enum), oai(rename_all = "snake_case"))]
+pub enum UnitOutcomeView {
+    Ok,
+    RetriedOk,
+    TransportError,
+    Timeout,
+    SchemaViolation,
+    ValidationRejected,
+}
+
+/// The lattice at one step. `states[x][y]` and `rules[x][y]` hold the unit at
+/// position `(x, y)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct StepGrid {
+    pub entity_id: String,
+    pub step: u32,
+    pub width: usize,
+    pub height: usize,
+    pub states: Vec<Vec<String>>,
+    pub rules: Vec<Vec<String>>,
+    /// Resolver id of each unit's species, empty for units without one.
+    pub species: Vec<Vec<String>>,
+    /// Author of the manual edit that produced this step, if it was one.
+    pub edited_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct StepResult {
+    pub step: u32,
+    pub telemetry: TelemetryView,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct UnitHistoryEntry {
+    pub step: u32,
+    /// RFC 3339.
+    pub timestamp: String,
+    pub rule: String,
+    pub state: String,
+    pub feedback: String,
+    pub outcome: UnitOutcomeView,
+    pub tokens: u64,
+    /// Resolver that produced the entry, if one was asked.
+    pub resolver_id: Option<String>,
+    /// Calls on other resolvers that failed first, oldest first.
+    pub attempts: Vec<ResolverAttemptView>,
+    /// Resolver a hedged request went to, if one was sent.
+    pub hedged_to: Option<String>,
+    /// Reused from the response cache instead of asked of the resolver.
+    pub cached: bool,
+    /// Author of the manual edit that set this entry, if it was one.
+    pub edited_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ResolverAttemptView {
+    pub resolver_id: String,
+    pub outcome: UnitOutcomeView,
+    pub latency_ms: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub feedback: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct UnitHistory {
+    pub entity_id: String,
+    pub x: usize,
+    pub y: usize,
+    pub entries: Vec<UnitHistoryEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct StepTelemetryEntry {
+    pub step: u32,
+    pub telemetry: TelemetryView,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct TelemetryPage {
+    pub entity_id: String,
+    /// Number of steps with telemetry; the page covers
+    /// `offset..offset + items.len()`.
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub items: Vec<StepTelemetryEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct RewindPayload {
+    pub step: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct RewindResult {
+    pub step: u32,
+    /// Archive holding the discarded steps, if there were any.
+    pub archive: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ArchiveList {
+    pub archives: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct RestoredArchive {
+    pub step: u32,
+}
+
+// Interventions
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct SetUnitAction {
+    pub x: usize,
+    pub y: usize,
+    pub rule: Option<String>,
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct FeedbackAction {
+    pub x: usize,
+    pub y: usize,
+    pub message: String,
+}
+
+/// Covers the rectangle between both corners, inclusive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct PaintAction {
+    pub from_x: usize,
+    pub from_y: usize,
+    pub to_x: usize,
+    pub to_y: usize,
+    pub rule: Option<String>,
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct BroadcastAction {
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+#[cfg_attr(feature = "openapi", derive(Union), oai(discriminator_name = "kind"))]
+pub enum InteractionAction {
+    #[serde(rename = "set_unit")]
+    #[cfg_attr(feature = "openapi", oai(mapping = "set_unit"))]
+    SetUnit(SetUnitAction),
+    #[serde(rename = "feedback")]
+    #[cfg_attr(feature = "openapi", oai(mapping = "feedback"))]
+    Feedback(FeedbackAction),
+    #[serde(rename = "paint")]
+    #[cfg_attr(feature = "openapi", oai(mapping = "paint"))]
+    Paint(PaintAction),
+    #[serde(rename = "broadcast")]
+    #[cfg_attr(feature = "openapi", oai(mapping = "broadcast"))]
+    Broadcast(BroadcastAction),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct InteractionPayload {
+    /// Recorded with the resulting step; `anonymous` when omitted. Replaced by
+    /// the caller's name when authentication is enabled.
+    pub author: Option<String>,
+    pub note: Option<String>,
+    pub actions: Vec<InteractionAction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct InterventionView {
+    pub step: u32,
+    /// RFC 3339.
+    pub applied_at: String,
+    pub author: String,
+    pub note: Option<String>,
+    pub actions: Vec<InteractionAction>,
+    pub units_changed: Vec<Position>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct InterventionResult {
+    pub step: u32,
+    pub intervention: InterventionView,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct InterventionLookup {
+    /// Unset for a computed step.
+    pub intervention: Option<InterventionView>,
+}
+
+// Runs
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct RunPayload {
+    /// Upper bound on the number of steps to compute.
+    pub steps: u32,
+    /// Stop once a step leaves every unit unchanged.
+    pub stop_on_fixed_point: Option<bool>,
+    /// Stop once fewer than this many distinct states remain.
+    pub unique_states_below: Option<usize>,
+    /// Stop once the run has spent this many tokens.
+    pub token_budget: Option<u64>,
+    /// Stop once the run has cost this much, per the resolvers' price tables.
+    pub cost_budget: Option<f64>,
+    /// Stop after this much wall-clock running time, excluding pauses.
+    pub max_duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Enum), oai(rename_all = "snake_case"))]
+pub enum RunState {
+    Running,
+    Paused,
+    Finished,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(Enum), oai(rename_all = "snake_case"))]
+pub enum StopReason {
+    StepsCompleted,
+    FixedPoint,
+    UniqueStatesBelow,
+    TokenBudgetExceeded,
+    CostBudgetExceeded,
+    TimeLimit,
+    QuotaExceeded,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct RunView {
+    pub id: String,
+    pub entity_id: String,
+    pub spec: RunPayload,
+    pub state: RunState,
+    pub stop_reason: Option<StopReason>,
+    pub error: Option<String>,
+    pub start_step: u32,
+    pub steps_completed: u32,
+    pub tokens_used: u64,
+    pub cost_used: f64,
+    /// Wall-clock time spent running, excluding pauses.
+    pub elapsed_ms: u64,
+    pub last_telemetry: Option<TelemetryView>,
+    /// RFC 3339.
+    pub started_at: String,
+    /// RFC 3339.
+    pub finished_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct RunList {
+    pub runs: Vec<RunView>,
+}
+
+// Live progress
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct StepStartedEvent {
+    pub entity_id: String,
+    pub step: u32,
+    pub units_total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct UnitCompletedEvent {
+    pub entity_id: String,
+    pub step: u32,
+    pub x: usize,
+    pub y: usize,
+    pub rule: String,
+    pub state: String,
+    pub feedback: String,
+    pub outcome: UnitOutcomeView,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct StepCompletedEvent {
+    pub entity_id: String,
+    pub step: u32,
+    pub telemetry: TelemetryView,
+}
+
+/// Sent instead of the events a slow client missed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct LaggedEvent {
+    pub skipped: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[cfg_attr(feature = "openapi", derive(Union), oai(discriminator_name = "type"))]
+pub enum StepEventView {
+    #[serde(rename = "step_started")]
+    #[cfg_attr(feature = "openapi", oai(mapping = "step_started"))]
+    StepStarted(StepStartedEvent),
+    #[serde(rename = "unit_completed")]
+    #[cfg_attr(feature = "openapi", oai(mapping = "unit_completed"))]
+    UnitCompleted(UnitCompletedEvent),
+    #[serde(rename = "step_completed")]
+    #[cfg_attr(feature = "openapi", oai(mapping = "step_completed"))]
+    StepCompleted(StepCompletedEvent),
+    #[serde(rename = "lagged")]
+    #[cfg_attr(feature = "openapi", oai(mapping = "lagged"))]
+    Lagged(LaggedEvent),
+}
+
+impl StepEventView {
+    /// The SSE event name, same as the `type` field.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StepEventView::StepStarted(_) => "step_started",
+            StepEventView::UnitCompleted(_) => "unit_completed",
+            StepEventView::StepCompleted(_) => "step_completed",
+            StepEventView::Lagged(_) => "lagged",
+        }
+    }
+}
+
+// Users
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct UserView {
+    pub name: String,
+    pub admin: bool,
+    pub steps_per_hour: Option<u32>,
+    pub tokens_per_day: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct UsageView {
+    pub steps_last_hour: usize,
+    pub tokens_last_day: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct Me {
+    pub user: UserView,
+    pub auth_enabled: bool,
+    pub usage: UsageView,
+}
+
+// Resolvers
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "openapi", derive(Enum), oai(rename_all = "lowercase"))]
+pub enum ProviderPayload {
+    Ollama,
+    OpenRouter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ResolverPayload {
+    /// Id of the new resolver; must not clash with an existing one.
+    pub name: String,
+    /// Inferred from `api_url` when omitted.
+    pub provider: Option<ProviderPayload>,
+    pub api_url: String,
+    pub model_name: String,
+    pub api_key: Option<String>,
+    /// Used to price the tokens the resolver spends.
+    pub pricing: Option<PricingPayload>,
+    /// Requests it can take at once, relative to the other resolvers; 1
+    /// when omitted.
+    pub capacity: Option<u32>,
+    /// Timeout, retries, rate limits and circuit breaker; none by default.
+    pub policy: Option<PolicyPayload>,
+}
+
+/// Price of a resolver's tokens, per million.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct PricingPayload {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// How requests to a resolver are limited and retried. Omitted fields keep
+/// their defaults: no timeout or rate limit, no retries, 500 ms backoff.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct PolicyPayload {
+    /// Finite and non-negative; zero means no timeout.
+    pub timeout_secs: Option<f64>,
+    /// Retries of transport errors and timeouts.
+    pub retries: Option<u32>,
+    /// Wait before the first retry, doubled for every following one.
+    pub backoff_ms: Option<u64>,
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u64>,
+    pub circuit_breaker: Option<CircuitBreakerPayload>,
+    /// Resolver ids a unit that failed here is retried on, in order.
+    pub failover: Option<Vec<String>>,
+    /// Also send a unit to the first failover resolver when this one has
+    /// not answered after this many milliseconds.
+    pub hedge_after_ms: Option<u64>,
+}
+
+/// Takes the resolver out of the rotation for `cooldown_secs` (default 60, at
+/// most a year) after `failures` consecutive failed units.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct CircuitBreakerPayload {
+    pub failures: u32,
+    pub cooldown_secs: Option<u64>,
+}
+
+/// A configured resolver. The API key is never returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ResolverView {
+    pub id: String,
+    pub name: Option<String>,
+    pub provider: ProviderPayload,
+    pub api_url: String,
+    pub model_name: String,
+    pub api_key_set: bool,
+    pub enabled: bool,
+    pub pricing: Option<PricingPayload>,
+    pub capacity: u32,
+    pub policy: PolicyPayload,
+    /// Whether its circuit breaker currently keeps units away from it.
+    pub circuit_open: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(Object))]
+pub struct ResolverHealthView {
+    pub resolver_id: String,
+    pub healthy: bool,
+    /// HTTP status of the model listing, if the API answered.
+    pub status: Option<u16>,
+    pub model_available: Option<bool>,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    /// RFC 3339.
+    pub checked_at: String,
+}
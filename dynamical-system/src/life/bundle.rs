@@ -0,0 +1,290 @@
+use std::{
+    collections::BTreeMap,
+    fs::read_dir,
+    io::{self, Read, Write},
+    path::{Component, Path},
+};
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use tracing::info;
+
+use super::{
+    entity::Entity, manager::is_safe_id, manifest::MANIFEST_FILE as ENTITY_MANIFEST_FILE,
+    storage::step_file_names, usage::USAGE_FILE,
+};
+
+pub const BUNDLE_EXTENSION: &str = "llmca.tar.zst";
+/// Decompressed size a bundle may unpack to.
+pub const MAX_BUNDLE_BYTES: u64 = 1024 * 1024 * 1024;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const FRAMES_FOLDER: &str = "frames";
+const ZSTD_LEVEL: i32 = 9;
+
+/// Describes the content of an entity bundle. Every file in the archive other
+/// than the manifest itself is listed with its size and md5 digest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub entity_id: String,
+    pub step: u32,
+    pub exported_at: DateTime<Utc>,
+    pub files: Vec<BundleFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub path: String,
+    pub size: u64,
+    pub md5: String,
+}
+
+impl BundleFile {
+    fn new(path: String, content: &[u8]) -> Self {
+        Self {
+            path,
+            size: content.len() as u64,
+            md5: format!("{:x}", md5::compute(content)),
+        }
+    }
+}
+
+/// Writes `entity` as a single zstd-compressed tar: `manifest.json`, every
+/// step snapshot with its sidecars, `entity.json` and `usage.json` if the
+/// entity has them and, if `include_frames` is set, the files under `frames/`.
+pub fn export_entity<W: Write>(
+    entity: &Entity,
+    writer: W,
+    include_frames: bool,
+) -> io::Result<BundleManifest> {
+    let folder = entity.artifacts_folder();
+
+    let mut files = entity
+        .saved_steps()
+        .into_iter()
+        .flat_map(|step| step_file_names(folder, step))
+        .collect::<Vec<_>>();
+
+    for file in [ENTITY_MANIFEST_FILE, USAGE_FILE] {
+        if folder.join(file).is_file() {
+            files.push(file.to_string());
+        }
+    }
+
+    if include_frames && folder.join(FRAMES_FOLDER).is_dir() {
+        for frame in read_dir(folder.join(FRAMES_FOLDER))? {
+            let frame = frame?;
+
+            if frame.path().is_file() {
+                files.push(format!(
+                    "{}/{}",
+                    FRAMES_FOLDER,
+                    frame.file_name().to_string_lossy()
+                ));
+            }
+        }
+    }
+
+    let contents = files
+        .into_iter()
+        .map(|path| std::fs::read(folder.join(&path)).map(|content| (path, content)))
+        .collect::<io::Result<BTreeMap<_, _>>>()?;
+
+    let manifest = BundleManifest {
+        entity_id: entity.id().to_string(),
+        step: entity.current_step(),
+        exported_at: Utc::now(),
+        files: contents
+            .iter()
+            .map(|(path, content)| BundleFile::new(path.clone(), content))
+            .collect(),
+    };
+
+    let encoder = zstd::Encoder::new(writer, ZSTD_LEVEL)?;
+    let mut archive = tar::Builder::new(encoder);
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    append_file(&mut archive, MANIFEST_FILE, &manifest_json)?;
+
+    for (path, content) in &contents {
+        append_file(&mut archive, path, content)?;
+    }
+
+    archive.into_inner()?.finish()?;
+
+    info!(
+        entity_id = %manifest.entity_id,
+        step = manifest.step,
+        files = manifest.files.len(),
+        include_frames,
+        "entity_exported"
+    );
+
+    Ok(manifest)
+}
+
+/// Reads and verifies a bundle produced by [`export_entity`]. Every file listed
+/// in the manifest must be present with a matching size and md5, and nothing
+/// outside the manifest is accepted, nor more than [`MAX_BUNDLE_BYTES`] once
+/// decompressed.
+pub fn read_bundle<R: Read>(reader: R) -> io::Result<(BundleManifest, BTreeMap<String, Vec<u8>>)> {
+    let decoder = zstd::Decoder::new(reader)?;
+    let mut archive = tar::Archive::new(decoder);
+
+    let mut contents = BTreeMap::new();
+    let mut unpacked = 0;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+
+        if !is_safe_path(Path::new(&path)) {
+            return Err(invalid_bundle(format!("unsafe path {path}")));
+        }
+
+        let mut content = vec![];
+        (&mut entry)
+            .take(MAX_BUNDLE_BYTES - unpacked + 1)
+            .read_to_end(&mut content)?;
+
+        unpacked += content.len() as u64;
+
+        if unpacked > MAX_BUNDLE_BYTES {
+            return Err(invalid_bundle(format!(
+                "bundle unpacks to more than {MAX_BUNDLE_BYTES} bytes"
+            )));
+        }
+
+        contents.insert(path, content);
+    }
+
+    let manifest: BundleManifest = contents
+        .remove(MANIFEST_FILE)
+        .ok_or_else(|| invalid_bundle("missing manifest.json".to_string()))
+        .and_then(|manifest| serde_json::from_slice(&manifest).map_err(io::Error::from))?;
+
+    if !is_safe_id(&manifest.entity_id) {
+        return Err(invalid_bundle(format!(
+            "invalid entity id {:?}",
+            manifest.entity_id
+        )));
+    }
+
+    for file in &manifest.files {
+        let content = contents
+            .get(&file.path)
+            .ok_or_else(|| invalid_bundle(format!("missing file {}", file.path)))?;
+
+        let actual = BundleFile::new(file.path.clone(), content);
+
+        if actual.size != file.size || actual.md5 != file.md5 {
+            return Err(invalid_bundle(format!(
+                "checksum mismatch for {} (expected {}, got {})",
+                file.path, file.md5, actual.md5
+            )));
+        }
+    }
+
+    if contents.len() != manifest.files.len() {
+        return Err(invalid_bundle(
+            "archive contains files not listed in the manifest".to_string(),
+        ));
+    }
+
+    Ok((manifest, contents))
+}
+
+fn append_file<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    content: &[u8],
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+
+    archive.append_data(&mut header, path, content)
+}
+
+fn is_safe_path(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn invalid_bundle(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::life::manager::tests::{spec, temp_manager};
+
+    /// A bundle of `contents` whose manifest names `entity_id`.
+    fn bundle_for(
+        mut manifest: BundleManifest,
+        contents: &BTreeMap<String, Vec<u8>>,
+        entity_id: &str,
+    ) -> Vec<u8> {
+        manifest.entity_id = entity_id.to_string();
+
+        let mut bundle = vec![];
+        let mut archive = tar::Builder::new(zstd::Encoder::new(&mut bundle, ZSTD_LEVEL).unwrap());
+
+        append_file(
+            &mut archive,
+            MANIFEST_FILE,
+            &serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        for (path, content) in contents {
+            append_file(&mut archive, path, content).unwrap();
+        }
+
+        archive.into_inner().unwrap().finish().unwrap();
+
+        bundle
+    }
+
+    #[test]
+    fn import_rejects_ids_that_are_not_a_single_folder() {
+        let (mut manager, root) = temp_manager("import-bundle-id");
+        let victim = manager
+            .create_entity(spec("#000000"))
+            .unwrap()
+            .id()
+            .to_string();
+
+        let mut exported = vec![];
+        export_entity(manager.get_entity(&victim).unwrap(), &mut exported, false).unwrap();
+        let (manifest, contents) = read_bundle(exported.as_slice()).unwrap();
+
+        let entries = |folder: &Path| {
+            let mut entries = read_dir(folder)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect::<Vec<_>>();
+            entries.sort();
+            entries
+        };
+        let root_before = entries(manager.root_folder());
+        let victim_before = entries(&manager.root_folder().join(&victim));
+
+        let nested = format!("{victim}/archive/x-1-0");
+
+        for entity_id in [nested.as_str(), "..", "../x", ""] {
+            let bundle = bundle_for(manifest.clone(), &contents, entity_id);
+            let err = manager.import_bundle(bundle.as_slice()).err().unwrap();
+
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{entity_id:?}");
+        }
+
+        assert_eq!(entries(manager.root_folder()), root_before);
+        assert_eq!(entries(&manager.root_folder().join(&victim)), victim_before);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use std::{num::NonZeroUsize, sync::Arc};
+
+use lru::LruCache;
+use tracing::debug;
+
+use crate::system::space::CognitiveSpaceWithMemory;
+
+pub const DEFAULT_SNAPSHOT_CACHE_SIZE: usize = 64;
+
+type SnapshotKey = (String, u32);
+
+/// Least-recently-used cache of parsed step snapshots, keyed by entity id and
+/// step, so scrubbing through history doesn't re-read and re-parse JSON.
+#[derive(Debug)]
+pub struct SnapshotCache {
+    entries: LruCache<SnapshotKey, Arc<CognitiveSpaceWithMemory>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for SnapshotCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SNAPSHOT_CACHE_SIZE)
+    }
+}
+
+impl SnapshotCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(&mut self, entity_id: &str, step: u32) -> Option<Arc<CognitiveSpaceWithMemory>> {
+        let space = self.entries.get(&(entity_id.to_string(), step)).cloned();
+
+        if space.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+
+        space
+    }
+
+    pub fn put(&mut self, entity_id: &str, step: u32, space: Arc<CognitiveSpaceWithMemory>) {
+        self.entries.put((entity_id.to_string(), step), space);
+    }
+
+    /// Drops every cached step of `entity_id` after `step`, used when those
+    /// snapshots are moved away or about to be rewritten.
+    pub fn invalidate_after(&mut self, entity_id: &str, step: u32) {
+        let stale = self
+            .entries
+            .iter()
+            .filter(|((id, cached_step), _)| id == entity_id && *cached_step > step)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        for key in &stale {
+            self.entries.pop(key);
+        }
+
+        debug!(
+            entity_id,
+            after_step = step,
+            invalidated = stale.len(),
+            "snapshot_cache_invalidated"
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
@@ -0,0 +1,182 @@
+use std::{env, io, path::PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::cache::DEFAULT_SNAPSHOT_CACHE_SIZE;
+use crate::system::response_cache::{DEFAULT_RESPONSE_CACHE_FILES, DEFAULT_RESPONSE_CACHE_SIZE};
+
+pub const ROOT_ENV: &str = "LLMCA_ROOT";
+pub const RESOLVERS_ENV: &str = "LLMCA_RESOLVERS";
+pub const WORKSPACE_ENV: &str = "LLMCA_WORKSPACE";
+pub const SNAPSHOT_CACHE_ENV: &str = "LLMCA_SNAPSHOT_CACHE";
+/// `0`, `false` or `off` turns the response cache off; a number sets how
+/// many answers it keeps in memory.
+pub const RESPONSE_CACHE_ENV: &str = "LLMCA_RESPONSE_CACHE";
+/// How many answers the response cache keeps on disk.
+pub const RESPONSE_CACHE_FILES_ENV: &str = "LLMCA_RESPONSE_CACHE_FILES";
+
+pub const WORKSPACES_FOLDER: &str = "workspaces";
+
+/// Where a [`super::manager::LifeManager`] keeps its entities and reads its
+/// resolvers from.
+///
+/// Resolution order is defaults, then `LLMCA_*` environment variables, then
+/// `--root`, `--resolvers` and `--workspace` command line flags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LifeManagerConfig {
+    pub root_folder: PathBuf,
+    pub resolvers_path: PathBuf,
+    pub workspace: Option<String>,
+    pub load_entities: bool,
+    pub snapshot_cache_size: usize,
+    /// Answers the response cache keeps in memory; 0 disables the cache.
+    #[serde(default)]
+    pub response_cache_size: usize,
+    /// Answers the response cache keeps on disk before the oldest go.
+    #[serde(default = "default_response_cache_files")]
+    pub response_cache_files: usize,
+}
+
+fn default_response_cache_files() -> usize {
+    DEFAULT_RESPONSE_CACHE_FILES
+}
+
+impl Default for LifeManagerConfig {
+    fn default() -> Self {
+        Self {
+            root_folder: PathBuf::from(".life"),
+            resolvers_path: PathBuf::from("resolvers.toml"),
+            workspace: None,
+            load_entities: true,
+            snapshot_cache_size: DEFAULT_SNAPSHOT_CACHE_SIZE,
+            response_cache_size: DEFAULT_RESPONSE_CACHE_SIZE,
+            response_cache_files: DEFAULT_RESPONSE_CACHE_FILES,
+        }
+    }
+}
+
+impl LifeManagerConfig {
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(root_folder) = env::var(ROOT_ENV) {
+            config.root_folder = PathBuf::from(root_folder);
+        }
+
+        if let Ok(resolvers_path) = env::var(RESOLVERS_ENV) {
+            config.resolvers_path = PathBuf::from(resolvers_path);
+        }
+
+        config.workspace = env::var(WORKSPACE_ENV)
+            .ok()
+            .filter(|workspace| !workspace.is_empty());
+
+        if let Some(snapshot_cache_size) = env::var(SNAPSHOT_CACHE_ENV)
+            .ok()
+            .and_then(|size| size.parse().ok())
+        {
+            config.snapshot_cache_size = snapshot_cache_size;
+        }
+
+        if let Ok(response_cache) = env::var(RESPONSE_CACHE_ENV) {
+            config.response_cache_size = match response_cache.trim().to_ascii_lowercase().as_str() {
+                "0" | "false" | "off" | "no" => 0,
+                size => size.parse().unwrap_or(DEFAULT_RESPONSE_CACHE_SIZE),
+            };
+        }
+
+        if let Some(response_cache_files) = env::var(RESPONSE_CACHE_FILES_ENV)
+            .ok()
+            .and_then(|files| files.parse().ok())
+        {
+            config.response_cache_files = response_cache_files;
+        }
+
+        config
+    }
+
+    /// Environment configuration overridden by the process arguments.
+    pub fn from_env_and_args() -> io::Result<Self> {
+        Self::from_env().with_args(env::args().skip(1))
+    }
+
+    /// Applies `--root`, `--resolvers` and `--workspace` flags, given either
+    /// as `--flag value` or `--flag=value`. Any other argument, or a flag
+    /// without its value, is an error.
+    pub fn with_args<I: IntoIterator<Item = String>>(mut self, args: I) -> io::Result<Self> {
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            if !matches!(flag.as_str(), "--root" | "--resolvers" | "--workspace") {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown argument {flag:?}"),
+                ));
+            }
+
+            let Some(value) = inline_value.or_else(|| args.next()) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{flag} needs a value"),
+                ));
+            };
+
+            match flag.as_str() {
+                "--root" => self.root_folder = PathBuf::from(value),
+                "--resolvers" => self.resolvers_path = PathBuf::from(value),
+                _ => self.workspace = Some(value).filter(|workspace| !workspace.is_empty()),
+            }
+        }
+
+        Ok(self)
+    }
+
+    pub fn with_root_folder(mut self, root_folder: impl Into<PathBuf>) -> Self {
+        self.root_folder = root_folder.into();
+        self
+    }
+
+    pub fn with_resolvers_path(mut self, resolvers_path: impl Into<PathBuf>) -> Self {
+        self.resolvers_path = resolvers_path.into();
+        self
+    }
+
+    pub fn with_workspace(mut self, workspace: Option<String>) -> Self {
+        self.workspace = workspace;
+        self
+    }
+
+    pub fn with_load_entities(mut self, load_entities: bool) -> Self {
+        self.load_entities = load_entities;
+        self
+    }
+
+    pub fn with_snapshot_cache_size(mut self, snapshot_cache_size: usize) -> Self {
+        self.snapshot_cache_size = snapshot_cache_size;
+        self
+    }
+
+    pub fn with_response_cache_size(mut self, response_cache_size: usize) -> Self {
+        self.response_cache_size = response_cache_size;
+        self
+    }
+
+    pub fn with_response_cache_files(mut self, response_cache_files: usize) -> Self {
+        self.response_cache_files = response_cache_files;
+        self
+    }
+
+    /// The folder entities are stored in: the root itself for the default
+    /// workspace, `<root>/workspaces/<name>` for a named one.
+    pub fn workspace_folder(&self) -> PathBuf {
+        match &self.workspace {
+            Some(workspace) => self.root_folder.join(WORKSPACES_FOLDER).join(workspace),
+            None => self.root_folder.clone(),
+        }
+    }
+}
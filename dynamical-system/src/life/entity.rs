@@ -1,42 +1,108 @@
-use std::{collections::HashSet, fs::read_dir, path::PathBuf, time};
+use std::{
+    collections::HashSet,
+    fs::read_dir,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time,
+};
 
 use serde_derive::{Deserialize, Serialize};
 
 use crate::system::{
-    space::{build_lattice_with_memory, CognitiveSpaceWithMemory, LLMResolver},
+    metrics,
+    space::{
+        build_lattice_with_topology, lattice_index, CognitiveSpaceWithMemory, LLMResolver,
+        StepOptions,
+    },
     telemetry::StepTelemetry,
-    unit_next::CognitiveUnitPair,
+    unit_next::{CognitiveUnitComplex, CognitiveUnitPair},
+};
+use tracing::{error, info, instrument, warn};
+
+use super::{
+    events::{EventPublisher, StepEvent, StepEventSender},
+    intervention::{Intervention, InterventionRecord},
+    manager::{is_safe_id, LifeManager, SharedManager},
+    manifest::{EntityManifest, EntitySpec},
+    storage::{
+        move_step_files, quarantine, remove_stale_tmp_files, sidecar_path, write_atomic,
+        CheckpointWriter, StepCheckpoint, TELEMETRY_SIDECAR,
+    },
+    usage::EntityUsage,
 };
-use tracing::{info, instrument};
 
-use super::manager::LifeManager;
+const ARCHIVE_FOLDER: &str = "archive";
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Only ever built by a [`LifeManager`], whose [`SharedManager`] it keeps,
+/// so it can be serialized but not deserialized.
+#[derive(Debug, Serialize, Clone)]
 pub struct Entity {
     _id: String,
     artifacts_folder: PathBuf,
     space: CognitiveSpaceWithMemory,
     step: u32,
     state: EntityState,
-    manager: Box<LifeManager>,
+    manifest: Option<EntityManifest>,
+    #[serde(default)]
+    usage: EntityUsage,
+    #[serde(skip)]
+    manager: Arc<SharedManager>,
+    #[serde(skip)]
+    events: Option<StepEventSender>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum EntityState {
     ComputingStep(u32),
     Idle,
 }
 
 impl Entity {
+    /// An entity with the default topology and assignment; see
+    /// [`Entity::from_spec`] for the ways it can fail.
     pub fn new_2d_lattice(
         manager: &LifeManager,
         initial_state: Vec<CognitiveUnitPair>,
         size: (usize, usize),
         temporal_memory_size: usize,
-    ) -> Self {
-        let space = build_lattice_with_memory(size.0, size.1, temporal_memory_size, |(x, y)| {
-            initial_state[(x + y * size.1) % initial_state.len()].clone()
-        });
+    ) -> io::Result<Self> {
+        let spec = EntitySpec {
+            size,
+            memory_size: temporal_memory_size,
+            initial_states: initial_state,
+            topology: Default::default(),
+            resolvers: None,
+            assignment: Default::default(),
+            species: None,
+            cache_responses: false,
+        };
+
+        Self::from_spec(manager, spec)
+    }
+
+    /// Validates `spec`, builds the lattice and persists both the manifest and
+    /// step 0.
+    pub fn from_spec(manager: &LifeManager, spec: EntitySpec) -> io::Result<Self> {
+        spec.validate(&manager.all_resolvers())?;
+
+        let size = spec.size;
+        let initial_state = &spec.initial_states;
+
+        let mut space = build_lattice_with_topology(
+            size.0,
+            size.1,
+            spec.memory_size,
+            spec.topology,
+            |position| initial_state[lattice_index(position, size) % initial_state.len()].clone(),
+        );
+
+        if let Some(species) = &spec.species {
+            for position in space.positions() {
+                space.unit_mut(position).unwrap().species =
+                    Some(species.species_at(position, size).to_string());
+            }
+        }
 
         let space_hash = format!("{:x}", md5::compute(space.serialize_in_pretty_json()));
 
@@ -45,9 +111,31 @@ impl Entity {
             .unwrap()
             .as_secs();
 
-        let id = format!("{}-{}", space_hash, timestamp);
+        let base_id = format!("{}-{}", space_hash, timestamp);
+
+        // Identical specs created within the same second get a numeric suffix.
+        let (id, artifacts_folder) = (1..)
+            .find_map(|n| {
+                let id = match n {
+                    1 => base_id.clone(),
+                    n => format!("{base_id}-{n}"),
+                };
+
+                match manager.try_register_entity(id.clone()) {
+                    Ok(folder) => Some(Ok((id, folder))),
+                    Err(err) if err.kind() == io::ErrorKind::AlreadyExists => None,
+                    Err(err) => Some(Err(err)),
+                }
+            })
+            .unwrap()?;
 
-        let artifacts_folder = manager.register_entity(id.clone());
+        let manifest = EntityManifest {
+            id: id.clone(),
+            created_at: chrono::Utc::now(),
+            spec,
+        };
+
+        manifest.save(&artifacts_folder)?;
 
         let step = 0;
 
@@ -57,53 +145,91 @@ impl Entity {
             space,
             step,
             state: EntityState::Idle,
-            manager: Box::new(manager.clone()),
+            manifest: Some(manifest),
+            usage: EntityUsage::default(),
+            manager: manager.shared(),
+            events: None,
         };
 
-        ent.save_serialized();
+        ent.save_serialized()?;
 
-        ent
+        Ok(ent)
     }
 
     pub fn id(&self) -> &str {
         &self._id
     }
 
-    pub fn save_serialized(&self) {
-        let ser_space = self.space.serialize_in_pretty_json();
+    pub fn artifacts_folder(&self) -> &PathBuf {
+        &self.artifacts_folder
+    }
 
-        std::fs::write(
-            self.artifacts_folder.join(format!("{}.json", self.step)),
-            ser_space,
-        )
-        .unwrap();
+    pub fn manifest(&self) -> Option<&EntityManifest> {
+        self.manifest.as_ref()
     }
 
-    pub fn open_saved(artifacts_folder: PathBuf, manager: LifeManager) -> Self {
-        let folder = read_dir(&artifacts_folder).unwrap();
+    /// Tokens and cost of every step computed so far, including rewound ones.
+    pub fn usage(&self) -> &EntityUsage {
+        &self.usage
+    }
 
-        let steps = folder
-            .map(|entry| {
-                let entry = entry.unwrap();
-                entry.path()
-            })
-            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
-            .map(|path| {
-                path.file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or_default()
-                    .to_string()
-            })
-            .filter_map(|entry| entry.parse::<u32>().ok())
-            .collect::<Vec<_>>();
+    /// The manager's resolvers, narrowed to this entity's selection.
+    pub fn resolvers(&self) -> Vec<LLMResolver> {
+        let resolvers = self.manager.resolvers();
 
-        let last_step = steps.iter().max().unwrap();
-        let last_step_path = artifacts_folder.join(format!("{}.json", last_step));
+        match &self.manifest {
+            Some(manifest) => manifest.select_resolvers(resolvers),
+            None => resolvers,
+        }
+    }
 
-        let json = std::fs::read_to_string(last_step_path).unwrap();
+    /// How units are spread over the resolvers, whether they inherit species
+    /// and reuse cached answers, from the manifest; the defaults, without the
+    /// cache, for entities without one.
+    pub fn step_options(&self) -> StepOptions {
+        let Some(manifest) = &self.manifest else {
+            return StepOptions::default();
+        };
 
-        let space = CognitiveSpaceWithMemory::load_from_json(&json);
+        StepOptions {
+            assignment: manifest.spec.assignment,
+            inherit_species: manifest
+                .spec
+                .species
+                .as_ref()
+                .is_some_and(|species| species.inherit),
+            response_cache: manifest
+                .spec
+                .cache_responses
+                .then(|| self.manager.response_cache())
+                .flatten(),
+        }
+    }
 
+    pub fn saved_steps(&self) -> Vec<u32> {
+        let mut steps = saved_steps(&self.artifacts_folder);
+        steps.sort();
+        steps
+    }
+
+    pub fn save_serialized(&self) -> io::Result<()> {
+        let ser_space = self.space.serialize_in_pretty_json();
+
+        write_atomic(
+            &self.artifacts_folder.join(format!("{}.json", self.step)),
+            ser_space.as_bytes(),
+        )?;
+
+        self.manager
+            .cache_snapshot(&self._id, self.step, Arc::new(self.space.clone()));
+
+        Ok(())
+    }
+
+    /// Opens the latest readable snapshot in `artifacts_folder`. Leftover temp
+    /// files are removed and snapshots that fail to parse are moved to
+    /// `quarantine/`, falling back to the previous step.
+    pub fn open_saved(artifacts_folder: PathBuf, manager: &LifeManager) -> io::Result<Self> {
         let id = artifacts_folder
             .file_name()
             .unwrap()
@@ -111,14 +237,62 @@ impl Entity {
             .unwrap()
             .to_string();
 
-        Self {
-            _id: id,
-            artifacts_folder,
-            space,
-            step: *last_step,
-            state: EntityState::Idle,
-            manager: Box::new(manager),
+        let stale_tmp_files = remove_stale_tmp_files(&artifacts_folder)?;
+
+        if stale_tmp_files > 0 {
+            warn!(entity_id = %id, stale_tmp_files, "entity_removed_stale_tmp_files");
+        }
+
+        let manifest = EntityManifest::load(&artifacts_folder).unwrap_or_else(|err| {
+            warn!(entity_id = %id, error = %err, "entity_manifest_unreadable");
+            None
+        });
+
+        let usage = EntityUsage::load(&artifacts_folder).unwrap_or_else(|err| {
+            warn!(entity_id = %id, error = %err, "entity_usage_unreadable");
+            EntityUsage::default()
+        });
+
+        let mut steps = saved_steps(&artifacts_folder);
+        steps.sort();
+
+        while let Some(last_step) = steps.pop() {
+            let last_step_path = artifacts_folder.join(format!("{}.json", last_step));
+
+            let json = std::fs::read_to_string(&last_step_path)?;
+
+            match CognitiveSpaceWithMemory::try_load_from_json(&json) {
+                Ok(space) => {
+                    return Ok(Self {
+                        _id: id,
+                        artifacts_folder,
+                        space,
+                        step: last_step,
+                        state: EntityState::Idle,
+                        manifest,
+                        usage,
+                        manager: manager.shared(),
+                        events: None,
+                    });
+                }
+                Err(err) => {
+                    let quarantined = quarantine(&artifacts_folder, &last_step_path)?;
+
+                    warn!(
+                        entity_id = %id,
+                        step = last_step,
+                        error = %err,
+                        quarantined = %quarantined.display(),
+                        "entity_snapshot_quarantined"
+                    );
+                }
+            }
         }
+
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("entity {id} has no readable snapshot"),
+        ))
     }
 
     pub fn current_step(&self) -> u32 {
@@ -129,11 +303,156 @@ impl Entity {
         &self.space
     }
 
-    pub fn load_space_at(&self, step: usize) -> CognitiveSpaceWithMemory {
-        let json =
-            std::fs::read_to_string(self.artifacts_folder.join(format!("{}.json", step))).unwrap();
+    /// Returns the snapshot saved at `step`, served from the shared snapshot
+    /// cache when possible.
+    pub fn load_space_at(&self, step: usize) -> Arc<CognitiveSpaceWithMemory> {
+        self.try_load_space_at(step as u32).unwrap()
+    }
+
+    pub fn try_load_space_at(&self, step: u32) -> io::Result<Arc<CognitiveSpaceWithMemory>> {
+        self.snapshots().try_load_space_at(step)
+    }
+
+    /// A reader over the saved steps that does not borrow the entity, so the
+    /// entity's lock can be released before snapshots are parsed.
+    pub fn snapshots(&self) -> SnapshotReader {
+        SnapshotReader {
+            entity_id: self._id.clone(),
+            artifacts_folder: self.artifacts_folder.clone(),
+            steps: self.saved_steps(),
+            manager: self.manager.clone(),
+        }
+    }
+
+    /// Moves every snapshot after `step` into a safety archive and resumes the
+    /// entity from `step`. Returns the archive folder, if any snapshot was
+    /// discarded, so the rewind can be undone with [`Entity::restore_archive`].
+    pub fn rewind_to(&mut self, step: u32) -> io::Result<Option<PathBuf>> {
+        if let EntityState::ComputingStep(computing) = self.state {
+            return Err(io::Error::other(format!(
+                "entity is computing step {computing}"
+            )));
+        }
+
+        if step > self.step {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("step {step} is ahead of current step {}", self.step),
+            ));
+        }
+
+        let space = self.try_load_space_at(step)?;
+
+        let timestamp = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        // Rewinds within the same second get a numeric suffix.
+        let name = format!("{}-{}-{}", timestamp, step, self.step);
+        let archive = (1..)
+            .map(|n| match n {
+                1 => self.archives_folder().join(&name),
+                n => self.archives_folder().join(format!("{name}-{n}")),
+            })
+            .find(|archive| !archive.exists())
+            .unwrap();
 
-        CognitiveSpaceWithMemory::load_from_json(&json)
+        let discarded = saved_steps(&self.artifacts_folder)
+            .into_iter()
+            .filter(|saved| *saved > step)
+            .collect::<Vec<_>>();
+
+        if !discarded.is_empty() {
+            std::fs::create_dir_all(&archive)?;
+        }
+
+        for saved in &discarded {
+            move_step_files(&self.artifacts_folder, &archive, *saved)?;
+        }
+
+        info!(
+            entity_id = %self._id,
+            from_step = self.step,
+            to_step = step,
+            archived_steps = discarded.len(),
+            archive = %archive.display(),
+            "entity_rewound"
+        );
+
+        self.manager.invalidate_snapshots_after(&self._id, step);
+        StepCheckpoint::remove(&self.artifacts_folder, self.step + 1);
+
+        self.space = (*space).clone();
+        self.step = step;
+        self.state = EntityState::Idle;
+
+        Ok((!discarded.is_empty()).then_some(archive))
+    }
+
+    /// Lists the safety archives created by [`Entity::rewind_to`], oldest first.
+    pub fn list_archives(&self) -> Vec<String> {
+        let Ok(folder) = read_dir(self.archives_folder()) else {
+            return vec![];
+        };
+
+        let mut archives = folder
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .collect::<Vec<_>>();
+
+        archives.sort();
+
+        archives
+    }
+
+    /// Undoes a rewind: steps computed since the archive was taken are archived
+    /// in turn, then the archived snapshots are moved back in place.
+    pub fn restore_archive(&mut self, name: &str) -> io::Result<()> {
+        let unknown = || io::Error::new(io::ErrorKind::NotFound, format!("unknown archive {name}"));
+
+        // Only names listed in the archives folder, so `name` cannot reach
+        // outside of it.
+        if !is_safe_id(name) || !self.list_archives().iter().any(|archive| archive == name) {
+            return Err(unknown());
+        }
+
+        let archive = self.archives_folder().join(name);
+
+        let base_step = name
+            .split('-')
+            .nth(1)
+            .and_then(|step| step.parse::<u32>().ok())
+            .ok_or_else(unknown)?;
+
+        self.rewind_to(base_step)?;
+
+        let restored = saved_steps(&archive);
+
+        for saved in &restored {
+            move_step_files(&archive, &self.artifacts_folder, *saved)?;
+        }
+
+        std::fs::remove_dir(&archive)?;
+
+        if let Some(last_step) = restored.into_iter().max() {
+            self.space = (*self.try_load_space_at(last_step)?).clone();
+            self.step = last_step;
+        }
+
+        info!(
+            entity_id = %self._id,
+            archive = name,
+            step = self.step,
+            "entity_archive_restored"
+        );
+
+        Ok(())
+    }
+
+    fn archives_folder(&self) -> PathBuf {
+        self.artifacts_folder.join(ARCHIVE_FOLDER)
     }
 
     pub fn calculate_unique_states(&self) -> HashSet<String> {
@@ -150,14 +469,65 @@ impl Entity {
     }
 
     #[instrument(skip_all, fields(entity_id = %self._id, current_step = self.step, next_step = self.step + 1))]
-    pub async fn evolve_async(&mut self) -> StepTelemetry {
+    pub async fn evolve_async(&mut self) -> io::Result<StepTelemetry> {
+        let resolvers = self.resolvers();
+
+        self.run_step(&resolvers).await
+    }
+
+    /// Computes the next step, resuming from the on-disk checkpoint of a
+    /// previously interrupted attempt if there is one. If the snapshot cannot
+    /// be saved the entity stays at its current step; the units computed so
+    /// far remain in the checkpoint for the next attempt.
+    async fn run_step(&mut self, resolvers: &[LLMResolver]) -> io::Result<StepTelemetry> {
         self.state = EntityState::ComputingStep(self.step + 1);
-        let telemetry = self.space.distributed_step(self.manager.resolvers()).await;
+        let previous = self.space.clone();
+
+        let checkpoint = StepCheckpoint::load(&self.artifacts_folder, self.step);
+        let completed = checkpoint.completed();
+
+        self.publish(StepEvent::StepStarted {
+            entity_id: self._id.clone(),
+            step: self.step + 1,
+            units_total: self.space.positions().len(),
+        });
+
+        let mut observer = (
+            CheckpointWriter::new(&self.artifacts_folder, checkpoint),
+            self.events
+                .clone()
+                .map(|sender| EventPublisher::new(self._id.clone(), self.step + 1, sender)),
+        );
+
+        let telemetry = self
+            .space
+            .distributed_step_with(resolvers, self.step_options(), completed, &mut observer)
+            .await;
+
         self.step += 1;
 
-        self.save_serialized();
+        if let Err(err) = self.save_serialized() {
+            error!(entity_id = %self._id, step = self.step, error = %err, "entity_snapshot_save_failed");
+
+            self.step -= 1;
+            self.space = previous;
+            self.state = EntityState::Idle;
+
+            return Err(err);
+        }
+
+        self.save_telemetry(&telemetry);
+        self.record_usage(&telemetry);
+        metrics::record_step(&self._id, &telemetry);
+        StepCheckpoint::remove(&self.artifacts_folder, self.step);
         self.state = EntityState::Idle;
 
+        self.publish(StepEvent::StepCompleted {
+            entity_id: self._id.clone(),
+            step: self.step,
+            telemetry: telemetry.clone(),
+        });
+
         info!(
             entity_id = %self._id,
             step = self.step,
@@ -166,11 +536,139 @@ impl Entity {
             unique_states = telemetry.unique_states,
             parse_failures = telemetry.parse_failures,
             llm_failures = telemetry.llm_failures,
+            total_tokens = telemetry.total_tokens,
+            cost = telemetry.cost,
+            cumulative_cost = self.usage.cost,
             elapsed_ms = telemetry.elapsed_ms,
             "entity_evolved"
         );
 
-        telemetry
+        Ok(telemetry)
+    }
+
+    /// Applies a manual edit and persists the result as a new step, together
+    /// with an [`InterventionRecord`] describing who changed what.
+    pub fn intervene(&mut self, intervention: Intervention) -> io::Result<InterventionRecord> {
+        if let EntityState::ComputingStep(computing) = self.state {
+            return Err(io::Error::other(format!(
+                "entity is computing step {computing}"
+            )));
+        }
+
+        let mut space = self.space.clone();
+        let units_changed = intervention.apply(&mut space)?;
+
+        let record = InterventionRecord {
+            step: self.step + 1,
+            applied_at: chrono::Utc::now(),
+            intervention,
+            units_changed,
+        };
+
+        record.save(&self.artifacts_folder)?;
+        StepCheckpoint::remove(&self.artifacts_folder, self.step + 1);
+
+        let previous = std::mem::replace(&mut self.space, space);
+        self.step += 1;
+
+        if let Err(err) = self.save_serialized() {
+            let _ =
+                std::fs::remove_file(InterventionRecord::path(&self.artifacts_folder, self.step));
+
+            self.step -= 1;
+            self.space = previous;
+
+            return Err(err);
+        }
+
+        info!(
+            entity_id = %self._id,
+            step = self.step,
+            author = %record.intervention.author,
+            actions = record.intervention.actions.len(),
+            units_changed = record.units_changed.len(),
+            "entity_intervened"
+        );
+
+        Ok(record)
+    }
+
+    /// The intervention that produced `step`, if it was a manual edit.
+    pub fn intervention_at(&self, step: u32) -> Option<InterventionRecord> {
+        self.snapshots().intervention_at(step)
+    }
+
+    fn save_telemetry(&self, telemetry: &StepTelemetry) {
+        let result = serde_json::to_vec_pretty(telemetry)
+            .map_err(io::Error::from)
+            .and_then(|json| {
+                write_atomic(
+                    &sidecar_path(&self.artifacts_folder, self.step, TELEMETRY_SIDECAR),
+                    &json,
+                )
+            });
+
+        if let Err(err) = result {
+            warn!(entity_id = %self._id, step = self.step, error = %err, "entity_telemetry_write_failed");
+        }
+    }
+
+    fn record_usage(&mut self, telemetry: &StepTelemetry) {
+        self.usage.record(telemetry);
+
+        if let Err(err) = self.usage.save(&self.artifacts_folder) {
+            warn!(entity_id = %self._id, step = self.step, error = %err, "entity_usage_write_failed");
+        }
+    }
+
+    /// Telemetry of the computed steps, oldest first, skipping `offset` and
+    /// returning at most `limit`, along with how many there are in total.
+    /// Step 0 and manual edits have none.
+    pub fn telemetry_history(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> (usize, Vec<(u32, StepTelemetry)>) {
+        let steps = self
+            .saved_steps()
+            .into_iter()
+            .filter(|step| sidecar_path(&self.artifacts_folder, *step, TELEMETRY_SIDECAR).is_file())
+            .collect::<Vec<_>>();
+
+        let page = steps
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .filter_map(|step| {
+                let path = sidecar_path(&self.artifacts_folder, *step, TELEMETRY_SIDECAR);
+                let json = std::fs::read_to_string(path).ok()?;
+
+                Some((*step, serde_json::from_str(&json).ok()?))
+            })
+            .collect();
+
+        (steps.len(), page)
+    }
+
+    /// The entry the unit at `position` ended each saved step with.
+    pub fn unit_history(&self, position: (usize, usize)) -> Vec<(u32, CognitiveUnitComplex)> {
+        self.snapshots().unit_history(position)
+    }
+
+    /// Publishes [`StepEvent`]s to `sender` while steps are computed. Clones of
+    /// the entity publish to the same channel.
+    pub fn set_event_sender(&mut self, sender: Option<StepEventSender>) {
+        self.events = sender;
+    }
+
+    pub fn event_sender(&self) -> Option<&StepEventSender> {
+        self.events.as_ref()
+    }
+
+    fn publish(&self, event: StepEvent) {
+        if let Some(sender) = &self.events {
+            let _ = sender.send(event);
+        }
     }
 
     pub fn state(&self) -> &EntityState {
@@ -181,47 +679,130 @@ impl Entity {
         self.state = state;
     }
 
-    pub async fn evolve(
-        &mut self,
-        handle: &tokio::runtime::Handle,
-        resolvers: &[LLMResolver],
-        // tx: Sender<bool>,
-        // self_outside: &mut Arc<Mutex<Self>>,
-    ) -> StepTelemetry {
-        self.state = EntityState::ComputingStep(self.step + 1);
-        // let entity_ptr = self.clone().to_owned(); // raw pointer is safe if you guarantee it won't drop
+    /// Computes the next step with `resolvers` instead of the manager's pool.
+    pub async fn evolve(&mut self, resolvers: &[LLMResolver]) -> io::Result<StepTelemetry> {
+        self.run_step(resolvers).await
+    }
+}
 
-        // let shared_self = Arc::new(Mutex::new(entity_ptr));
+/// The saved steps of an entity as of [`Entity::snapshots`].
+#[derive(Debug, Clone)]
+pub struct SnapshotReader {
+    entity_id: String,
+    artifacts_folder: PathBuf,
+    steps: Vec<u32>,
+    manager: Arc<SharedManager>,
+}
 
-        // let resolvers = load_llm_resolvers_from_env();
+impl SnapshotReader {
+    pub fn steps(&self) -> &[u32] {
+        &self.steps
+    }
 
-        // let cloned_self = Arc::clone(&shared_self);
+    /// Returns the snapshot saved at `step`, served from the shared snapshot
+    /// cache when possible.
+    pub fn try_load_space_at(&self, step: u32) -> io::Result<Arc<CognitiveSpaceWithMemory>> {
+        self.manager.snapshot(&self.entity_id, step, || {
+            let json =
+                std::fs::read_to_string(self.artifacts_folder.join(format!("{}.json", step)))?;
+
+            CognitiveSpaceWithMemory::try_load_from_json(&json).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("step {step} is unreadable: {err}"),
+                )
+            })
+        })
+    }
 
-        let telemetry = self
-            .space
-            .distributed_step_with_tasks(resolvers, handle)
-            .await;
+    /// The intervention that produced `step`, if it was a manual edit.
+    pub fn intervention_at(&self, step: u32) -> Option<InterventionRecord> {
+        InterventionRecord::load(&self.artifacts_folder, step).unwrap_or_else(|err| {
+            warn!(entity_id = %self.entity_id, step, error = %err, "entity_intervention_unreadable");
+            None
+        })
+    }
 
-        // let mut self_locked = self.lock().await;
+    /// The entry the unit at `position` ended each saved step with.
+    pub fn unit_history(&self, position: (usize, usize)) -> Vec<(u32, CognitiveUnitComplex)> {
+        self.steps
+            .iter()
+            .filter_map(|&step| {
+                let space = self.try_load_space_at(step).ok()?;
+                let unit = space
+                    .get_units()
+                    .into_iter()
+                    .find(|unit| unit.position == position)?;
+
+                Some((step, unit.memory.last()?.clone()))
+            })
+            .collect()
+    }
+}
 
-        self.step += 1;
-        self.save_serialized();
-        self.state = EntityState::Idle;
+/// Steps saved in `folder`, in no particular order; none if the folder is
+/// missing or unreadable.
+pub(crate) fn saved_steps(folder: &Path) -> Vec<u32> {
+    let Ok(entries) = read_dir(folder) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+        .map(|path| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_string()
+        })
+        .filter_map(|entry| entry.parse::<u32>().ok())
+        .collect()
+}
 
-        // tx.send(true).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::life::manager::tests::{spec, temp_manager};
 
-        info!(
-            entity_id = %self._id,
-            step = self.step,
-            units_total = telemetry.units_total,
-            units_completed = telemetry.units_completed,
-            unique_states = telemetry.unique_states,
-            parse_failures = telemetry.parse_failures,
-            llm_failures = telemetry.llm_failures,
-            elapsed_ms = telemetry.elapsed_ms,
-            "entity_evolved"
-        );
+    #[test]
+    fn restore_archive_rejects_names_outside_the_archives() {
+        let (mut manager, root) = temp_manager("restore-archive");
+        let victim = manager
+            .create_entity(spec("#000000"))
+            .unwrap()
+            .id()
+            .to_string();
+        let mut entity = Entity::from_spec(&manager, spec("#ffffff")).unwrap();
+
+        manager.try_register_entity("a-0-".to_string()).unwrap();
+        std::fs::create_dir_all(entity.archives_folder().join("1-0-1/nested-0-1")).unwrap();
+
+        let traversal = format!("../../a-0-/../{victim}");
+
+        for name in [traversal.as_str(), "1-0-1/nested-0-1", "..", "."] {
+            let err = entity.restore_archive(name).unwrap_err();
+
+            assert_eq!(err.kind(), io::ErrorKind::NotFound, "{name}");
+        }
+
+        let victim_folder = manager.root_folder().join(&victim);
+        assert!(victim_folder.join("0.json").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn malformed_snapshots_are_invalid_data() {
+        let (manager, root) = temp_manager("malformed-snapshot");
+        let entity = Entity::from_spec(&manager, spec("#000000")).unwrap();
+
+        std::fs::write(entity.artifacts_folder().join("1.json"), "{\"graph\":").unwrap();
+
+        let err = entity.snapshots().try_load_space_at(1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
 
-        telemetry
+        std::fs::remove_dir_all(root).unwrap();
     }
 }
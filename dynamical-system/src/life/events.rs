@@ -0,0 +1,75 @@
+use serde_derive::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::system::{
+    space::StepObserver,
+    telemetry::StepTelemetry,
+    unit_next::{CognitiveUnitComplex, UnitOutcome},
+};
+
+/// Events buffered per entity before slow subscribers start missing some.
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Progress of a step, published while it is being computed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StepEvent {
+    StepStarted {
+        entity_id: String,
+        step: u32,
+        units_total: usize,
+    },
+    UnitCompleted {
+        entity_id: String,
+        step: u32,
+        position: (usize, usize),
+        rule: String,
+        state: String,
+        feedback: String,
+        outcome: UnitOutcome,
+    },
+    StepCompleted {
+        entity_id: String,
+        step: u32,
+        telemetry: StepTelemetry,
+    },
+}
+
+pub type StepEventSender = broadcast::Sender<StepEvent>;
+
+pub fn step_event_channel() -> StepEventSender {
+    broadcast::channel(EVENT_CHANNEL_CAPACITY).0
+}
+
+/// [`StepObserver`] that publishes every completed unit as a
+/// [`StepEvent::UnitCompleted`].
+pub struct EventPublisher {
+    entity_id: String,
+    step: u32,
+    sender: StepEventSender,
+}
+
+impl EventPublisher {
+    pub fn new(entity_id: String, step: u32, sender: StepEventSender) -> Self {
+        Self {
+            entity_id,
+            step,
+            sender,
+        }
+    }
+}
+
+impl StepObserver for EventPublisher {
+    fn unit_completed(&mut self, position: (usize, usize), unit: &CognitiveUnitComplex) {
+        // Nobody listening is not an error.
+        let _ = self.sender.send(StepEvent::UnitCompleted {
+            entity_id: self.entity_id.clone(),
+            step: self.step,
+            position,
+            rule: unit.rule.clone(),
+            state: unit.state.clone(),
+            feedback: unit.feedback.clone(),
+            outcome: unit.outcome,
+        });
+    }
+}
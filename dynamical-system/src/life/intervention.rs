@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::system::{
+    space::CognitiveSpaceWithMemory,
+    unit_next::{CognitiveUnitComplex, UnitOutcome},
+};
+
+use super::storage::{sidecar_path, write_atomic, INTERVENTION_SIDECAR};
+
+pub const DEFAULT_AUTHOR: &str = "anonymous";
+
+/// A single edit applied to the lattice between two computed steps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InterventionAction {
+    /// Overrides the rule and/or state of one unit.
+    SetUnit {
+        position: (usize, usize),
+        rule: Option<String>,
+        state: Option<String>,
+    },
+    /// Leaves a message in the feedback of one unit.
+    Feedback {
+        position: (usize, usize),
+        message: String,
+    },
+    /// Overrides the rule and/or state of every unit in the rectangle spanned
+    /// by `from` and `to`, both inclusive.
+    Paint {
+        from: (usize, usize),
+        to: (usize, usize),
+        rule: Option<String>,
+        state: Option<String>,
+    },
+    /// Leaves a message in the feedback of every unit.
+    Broadcast { message: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Intervention {
+    pub author: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    pub actions: Vec<InterventionAction>,
+}
+
+/// Persisted as `<step>.intervention.json` next to the step it produced, so a
+/// replay can tell computed steps from manual edits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterventionRecord {
+    pub step: u32,
+    pub applied_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub intervention: Intervention,
+    pub units_changed: Vec<(usize, usize)>,
+}
+
+impl Intervention {
+    /// Applies every action to `space`, adding one memory entry per touched
+    /// unit. Nothing is changed if any action is invalid.
+    pub fn apply(&self, space: &mut CognitiveSpaceWithMemory) -> io::Result<Vec<(usize, usize)>> {
+        if self.actions.is_empty() {
+            return Err(invalid(
+                "an intervention needs at least one action".to_string(),
+            ));
+        }
+
+        let mut units = space.units_by_position_mut();
+        let mut drafts = BTreeMap::<(usize, usize), CognitiveUnitComplex>::new();
+
+        for action in &self.actions {
+            let targets = match action {
+                InterventionAction::SetUnit { position, .. }
+                | InterventionAction::Feedback { position, .. } => {
+                    if !units.contains_key(position) {
+                        return Err(invalid(format!("no unit at {position:?}")));
+                    }
+
+                    vec![*position]
+                }
+                InterventionAction::Paint { from, to, .. } => {
+                    let (min, max) = (
+                        (from.0.min(to.0), from.1.min(to.1)),
+                        (from.0.max(to.0), from.1.max(to.1)),
+                    );
+
+                    let targets = units
+                        .keys()
+                        .copied()
+                        .filter(|p| {
+                            (min.0..=max.0).contains(&p.0) && (min.1..=max.1).contains(&p.1)
+                        })
+                        .collect::<Vec<_>>();
+
+                    if targets.is_empty() {
+                        return Err(invalid(format!("no units between {from:?} and {to:?}")));
+                    }
+
+                    targets
+                }
+                InterventionAction::Broadcast { .. } => units.keys().copied().collect(),
+            };
+
+            for position in targets {
+                let draft = drafts.entry(position).or_insert_with(|| {
+                    let last = units
+                        .get(&position)
+                        .and_then(|unit| unit.memory.last().cloned())
+                        .unwrap_or_default();
+
+                    CognitiveUnitComplex {
+                        timestamp: Utc::now(),
+                        feedback: "".to_string(),
+                        tokens: 0,
+                        input_tokens: 0,
+                        output_tokens: 0,
+                        resolver_id: None,
+                        latency_ms: 0,
+                        outcome: UnitOutcome::Ok,
+                        attempts: vec![],
+                        hedged_to: None,
+                        cached: false,
+                        ..last
+                    }
+                });
+
+                action.apply_to(draft);
+            }
+        }
+
+        for (position, draft) in &drafts {
+            units.get_mut(position).unwrap().add_memory(draft.clone());
+        }
+
+        Ok(drafts.into_keys().collect())
+    }
+}
+
+impl InterventionAction {
+    fn apply_to(&self, unit: &mut CognitiveUnitComplex) {
+        match self {
+            InterventionAction::SetUnit { rule, state, .. }
+            | InterventionAction::Paint { rule, state, .. } => {
+                if let Some(rule) = rule {
+                    unit.rule = rule.clone();
+                }
+
+                if let Some(state) = state {
+                    unit.state = state.clone();
+                }
+            }
+            InterventionAction::Feedback { message, .. }
+            | InterventionAction::Broadcast { message } => {
+                if !unit.feedback.is_empty() {
+                    unit.feedback.push('\n');
+                }
+
+                unit.feedback.push_str(message);
+            }
+        }
+    }
+}
+
+impl InterventionRecord {
+    pub fn path(folder: &Path, step: u32) -> PathBuf {
+        sidecar_path(folder, step, INTERVENTION_SIDECAR)
+    }
+
+    pub fn load(folder: &Path, step: u32) -> io::Result<Option<Self>> {
+        let path = Self::path(folder, step);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let json = std::fs::read_to_string(path)?;
+
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    pub fn save(&self, folder: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+
+        write_atomic(&Self::path(folder, self.step), &json)
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -1,77 +1,250 @@
-use std::{path::PathBuf, time::Instant};
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Instant,
+};
 
-use super::entity::Entity;
-use crate::system::space::{load_llm_resolvers_from_toml, LLMResolver};
+use super::{
+    bundle::read_bundle,
+    cache::SnapshotCache,
+    config::{LifeManagerConfig, WORKSPACES_FOLDER},
+    entity::{saved_steps, Entity},
+    manifest::EntitySpec,
+    storage::write_atomic,
+};
+use crate::system::{
+    response_cache::{ResponseCache, RESPONSE_CACHE_FOLDER},
+    space::{
+        load_llm_resolvers_from_toml, save_llm_resolvers_to_toml, try_load_llm_resolvers_from_toml,
+        CognitiveSpaceWithMemory, LLMResolver,
+    },
+};
 use serde_derive::{Deserialize, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Folders of imports still being unpacked start with this.
+const IMPORT_STAGING_PREFIX: &str = ".import-";
+
+/// Serializable for inspection; a manager is only built by
+/// [`LifeManager::with_config`], which sets up its [`SharedManager`].
+#[derive(Debug, Serialize, Clone)]
 pub struct LifeManager {
+    config: LifeManagerConfig,
     root_folder: PathBuf,
+    handles: Vec<EntityHandle>,
     loaded_entities: Vec<Entity>,
-    resolvers: Vec<LLMResolver>,
+    #[serde(skip)]
+    shared: Arc<SharedManager>,
+}
+
+/// What the manager knows about an entity without opening any snapshot.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntityHandle {
+    pub id: String,
+    pub artifacts_folder: PathBuf,
+    pub latest_step: Option<u32>,
+}
+
+impl EntityHandle {
+    fn scan(artifacts_folder: PathBuf) -> Self {
+        let id = artifacts_folder
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let latest_step = saved_steps(&artifacts_folder).into_iter().max();
+
+        Self {
+            id,
+            artifacts_folder,
+            latest_step,
+        }
+    }
+}
+
+/// State shared by a manager, its clones and every entity they hand out:
+/// the resolver pool, the snapshot cache and the response cache.
+///
+/// Entities read the pool when a step starts, so a replaced pool is picked
+/// up by their next step while steps already running keep the old one.
+#[derive(Debug)]
+pub struct SharedManager {
+    resolvers: RwLock<Vec<LLMResolver>>,
+    snapshots: Mutex<SnapshotCache>,
+    responses: Option<Arc<ResponseCache>>,
 }
 
-impl Default for LifeManager {
-    fn default() -> Self {
-        Self::new(true)
+impl SharedManager {
+    fn new(
+        resolvers: Vec<LLMResolver>,
+        snapshot_cache_size: usize,
+        responses: Option<ResponseCache>,
+    ) -> Self {
+        Self {
+            resolvers: RwLock::new(resolvers),
+            snapshots: Mutex::new(SnapshotCache::new(snapshot_cache_size)),
+            responses: responses.map(Arc::new),
+        }
+    }
+
+    /// The response cache, unless it is disabled.
+    pub fn response_cache(&self) -> Option<Arc<ResponseCache>> {
+        self.responses.clone()
+    }
+
+    /// The enabled resolvers.
+    pub fn resolvers(&self) -> Vec<LLMResolver> {
+        self.resolvers
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|resolver| resolver.is_enabled())
+            .cloned()
+            .collect()
+    }
+
+    /// Every configured resolver, including disabled ones.
+    pub fn all_resolvers(&self) -> Vec<LLMResolver> {
+        self.resolvers.read().unwrap().clone()
+    }
+
+    /// Replaces the pool. Resolvers keep their runtime policy state across
+    /// the replacement, matched by id.
+    pub fn set_resolvers(&self, mut resolvers: Vec<LLMResolver>) {
+        let mut current = self.resolvers.write().unwrap();
+
+        for resolver in &mut resolvers {
+            resolver.adopt_guard(&current);
+        }
+
+        *current = resolvers;
+    }
+
+    /// Returns the cached snapshot or parses it with `load` and caches it.
+    pub fn snapshot(
+        &self,
+        entity_id: &str,
+        step: u32,
+        load: impl FnOnce() -> io::Result<CognitiveSpaceWithMemory>,
+    ) -> io::Result<Arc<CognitiveSpaceWithMemory>> {
+        if let Some(space) = self.snapshots.lock().unwrap().get(entity_id, step) {
+            return Ok(space);
+        }
+
+        let space = Arc::new(load()?);
+
+        self.snapshots
+            .lock()
+            .unwrap()
+            .put(entity_id, step, space.clone());
+
+        Ok(space)
+    }
+
+    pub fn cache_snapshot(&self, entity_id: &str, step: u32, space: Arc<CognitiveSpaceWithMemory>) {
+        self.snapshots.lock().unwrap().put(entity_id, step, space);
+    }
+
+    pub fn invalidate_snapshots_after(&self, entity_id: &str, step: u32) {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .invalidate_after(entity_id, step);
+    }
+
+    pub fn snapshot_cache_stats(&self) -> (usize, u64, u64) {
+        let snapshots = self.snapshots.lock().unwrap();
+
+        (snapshots.len(), snapshots.hits(), snapshots.misses())
     }
 }
 
 impl LifeManager {
-    pub fn new(load_entities: bool) -> Self {
+    pub fn new(load_entities: bool) -> io::Result<Self> {
+        Self::with_config(LifeManagerConfig::from_env().with_load_entities(load_entities))
+    }
+
+    /// Builds a manager for `config`. With `load_entities` set, entity folders
+    /// are indexed as [`EntityHandle`]s; snapshots are only parsed when an
+    /// entity is first requested. Fails on an invalid workspace name or if
+    /// the root folder cannot be created.
+    pub fn with_config(config: LifeManagerConfig) -> io::Result<Self> {
         let started_at = Instant::now();
-        let root_folder = PathBuf::from(".life");
+
+        if let Some(workspace) = &config.workspace {
+            if !is_safe_id(workspace) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "invalid workspace name {workspace:?}: it must be a single folder name"
+                    ),
+                ));
+            }
+        }
+
+        let root_folder = config.workspace_folder();
 
         if !root_folder.exists() {
-            std::fs::create_dir(&root_folder).unwrap();
+            std::fs::create_dir_all(&root_folder)?;
         }
 
         let resolver_started_at = Instant::now();
-        let resolvers = load_llm_resolvers_from_toml("resolvers.toml");
+        let resolvers = load_llm_resolvers_from_toml(&config.resolvers_path);
         debug!(
+            resolvers_path = %config.resolvers_path.display(),
             resolver_count = resolvers.len(),
             elapsed_ms = resolver_started_at.elapsed().as_millis() as u64,
             "life_manager_resolvers_loaded"
         );
 
+        let load_entities = config.load_entities;
+        // Answers do not depend on the workspace, so they are kept at the root.
+        let responses = (config.response_cache_size > 0).then(|| {
+            ResponseCache::new(
+                config.response_cache_size,
+                Some(config.root_folder.join(RESPONSE_CACHE_FOLDER)),
+            )
+            .with_max_files(config.response_cache_files)
+        });
+        let shared = Arc::new(SharedManager::new(
+            resolvers,
+            config.snapshot_cache_size,
+            responses,
+        ));
+
         let mut instance = Self {
+            config,
             root_folder,
-            resolvers,
+            handles: vec![],
             loaded_entities: vec![],
+            shared,
         };
 
         if load_entities {
-            let entity_folders = instance.list_entities();
-
-            for entity_folder in entity_folders {
-                let entity_started_at = Instant::now();
-                let folder_name = instance.root_folder.join(&entity_folder);
-                debug!(entity_folder = %entity_folder, "life_manager_loading_entity");
-
-                let entity = Entity::open_saved(folder_name, instance.clone());
-                instance.loaded_entities.push(entity);
-
-                debug!(
-                    entity_folder = %entity_folder,
-                    elapsed_ms = entity_started_at.elapsed().as_millis() as u64,
-                    "life_manager_entity_loaded"
-                );
-            }
+            instance.handles = instance
+                .list_entities()
+                .into_iter()
+                .map(|entity_folder| EntityHandle::scan(instance.root_folder.join(entity_folder)))
+                .collect();
         }
 
         info!(
-            loaded_entities = instance.loaded_entities.len(),
-            resolver_count = instance.resolvers.len(),
+            root_folder = %instance.root_folder.display(),
+            workspace = instance.config.workspace.as_deref().unwrap_or_default(),
+            indexed_entities = instance.handles.len(),
+            resolver_count = instance.resolvers().len(),
             load_entities,
             elapsed_ms = started_at.elapsed().as_millis() as u64,
             "life_manager_initialized"
         );
 
-        instance
+        Ok(instance)
     }
 
-    pub fn without_loaded_entities() -> Self {
+    pub fn without_loaded_entities() -> io::Result<Self> {
         Self::new(false)
     }
 
@@ -79,49 +252,333 @@ impl LifeManager {
         &self.root_folder
     }
 
+    pub fn config(&self) -> &LifeManagerConfig {
+        &self.config
+    }
+
+    pub fn shared(&self) -> Arc<SharedManager> {
+        self.shared.clone()
+    }
+
+    /// Named workspaces found under `<root>/workspaces`.
+    pub fn list_workspaces(&self) -> Vec<String> {
+        let Ok(folder) = std::fs::read_dir(self.config.root_folder.join(WORKSPACES_FOLDER)) else {
+            return vec![];
+        };
+
+        let mut workspaces = folder
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .collect::<Vec<_>>();
+
+        workspaces.sort();
+
+        workspaces
+    }
+
     pub fn register_entity(&self, id: String) -> PathBuf {
+        self.try_register_entity(id).unwrap()
+    }
+
+    pub fn try_register_entity(&self, id: String) -> io::Result<PathBuf> {
+        if !is_safe_id(&id) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid entity id {id:?}"),
+            ));
+        }
+
         let entity_folder = self.root_folder.join(&id);
 
-        std::fs::create_dir(&entity_folder).unwrap();
+        std::fs::create_dir(&entity_folder)?;
+
+        Ok(entity_folder)
+    }
+
+    /// Builds a new entity from `spec` and keeps it loaded.
+    pub fn create_entity(&mut self, spec: EntitySpec) -> io::Result<&mut Entity> {
+        let entity = Entity::from_spec(self, spec)?;
+
+        info!(
+            entity_id = entity.id(),
+            step = entity.current_step(),
+            "life_manager_entity_created"
+        );
+
+        self.handles
+            .push(EntityHandle::scan(entity.artifacts_folder().clone()));
+        self.loaded_entities.push(entity);
 
-        entity_folder
+        Ok(self.loaded_entities.last_mut().unwrap())
     }
 
+    /// Entity folders in the root; unreadable entries and names that are not
+    /// UTF-8 are skipped.
     pub fn list_entities(&self) -> Vec<String> {
-        std::fs::read_dir(&self.root_folder)
-            .unwrap()
-            .map(|entry| {
-                entry
-                    .unwrap()
-                    .path()
-                    .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string()
-            })
+        let Ok(folder) = std::fs::read_dir(&self.root_folder) else {
+            return vec![];
+        };
+
+        folder
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .filter(|name| self.config.workspace.is_some() || name != WORKSPACES_FOLDER)
+            .filter(|name| !name.starts_with(IMPORT_STAGING_PREFIX))
+            .filter(|name| name != RESPONSE_CACHE_FOLDER)
             .collect()
     }
 
+    pub fn entity_handles(&self) -> &Vec<EntityHandle> {
+        &self.handles
+    }
+
+    /// Unpacks a bundle written by [`super::bundle::export_entity`] into the
+    /// root folder and loads the entity. Fails without touching the disk if
+    /// the bundle does not verify or the entity already exists, and without
+    /// leaving a partial entity behind if unpacking fails.
+    pub fn import_bundle<R: io::Read>(&mut self, reader: R) -> io::Result<&Entity> {
+        let (manifest, contents) = read_bundle(reader)?;
+
+        if !contents.keys().any(|path| {
+            path.strip_suffix(".json")
+                .is_some_and(|step| step.parse::<u32>().is_ok())
+        }) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "bundle contains no step snapshots",
+            ));
+        }
+
+        let entity_folder = self.root_folder.join(&manifest.entity_id);
+
+        if entity_folder.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("entity {} already exists", manifest.entity_id),
+            ));
+        }
+
+        // Unpacked next to the entities and renamed into place, so a failed
+        // import leaves nothing behind under the entity's id.
+        let staging = self.root_folder.join(format!(
+            "{IMPORT_STAGING_PREFIX}{}-{}",
+            manifest.entity_id,
+            std::process::id()
+        ));
+
+        let unpacked = contents.iter().try_for_each(|(path, content)| {
+            let target = staging.join(path);
+
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            write_atomic(&target, content)
+        });
+
+        if let Err(err) = unpacked.and_then(|_| std::fs::rename(&staging, &entity_folder)) {
+            let _ = std::fs::remove_dir_all(&staging);
+
+            return Err(err);
+        }
+
+        info!(
+            entity_id = %manifest.entity_id,
+            step = manifest.step,
+            files = manifest.files.len(),
+            "entity_imported"
+        );
+
+        self.load_entity(&manifest.entity_id)
+            .map(|entity| &*entity)
+            .ok_or_else(|| io::Error::other("imported entity could not be loaded"))
+    }
+
+    /// Opens an entity from disk the first time it is requested. Returns
+    /// `None` if no folder with saved steps exists for `id`.
+    pub fn load_entity(&mut self, id: &str) -> Option<&mut Entity> {
+        if let Some(index) = self.loaded_entities.iter().position(|e| e.id() == id) {
+            return self.loaded_entities.get_mut(index);
+        }
+
+        let entity = self.open_entity(id)?;
+        let entity_folder = entity.artifacts_folder().clone();
+
+        self.handles.retain(|handle| handle.id != id);
+        self.handles.push(EntityHandle::scan(entity_folder));
+        self.loaded_entities.push(entity);
+
+        self.loaded_entities.last_mut()
+    }
+
+    /// Loads the entity if needed and hands ownership to the caller, e.g. a
+    /// registry that puts each entity behind its own lock.
+    pub fn take_entity(&mut self, id: &str) -> Option<Entity> {
+        self.load_entity(id)?;
+
+        let index = self.loaded_entities.iter().position(|e| e.id() == id)?;
+
+        Some(self.loaded_entities.remove(index))
+    }
+
+    /// Opens an entity from disk without keeping it, for callers that hold
+    /// their own entities; its snapshots are still read through the shared
+    /// snapshot cache. Returns `None` if no folder with saved steps exists
+    /// for `id` or its snapshots cannot be read.
+    pub fn open_entity(&self, id: &str) -> Option<Entity> {
+        let entity_folder = self.root_folder.join(id);
+
+        if !is_safe_id(id) || !entity_folder.is_dir() || saved_steps(&entity_folder).is_empty() {
+            return None;
+        }
+
+        let started_at = Instant::now();
+        let entity = match Entity::open_saved(entity_folder, self) {
+            Ok(entity) => entity,
+            Err(err) => {
+                warn!(entity_id = id, error = %err, "life_manager_entity_load_failed");
+                return None;
+            }
+        };
+
+        debug!(
+            entity_id = id,
+            step = entity.current_step(),
+            elapsed_ms = started_at.elapsed().as_millis() as u64,
+            "life_manager_entity_loaded"
+        );
+
+        Some(entity)
+    }
+
+    /// Returns the entity if it has already been loaded; see
+    /// [`LifeManager::load_entity`] to open it from disk.
     pub fn get_entity(&self, id: &str) -> Option<&Entity> {
         self.loaded_entities.iter().find(|entity| entity.id() == id)
     }
 
+    /// Returns the entity, loading it from disk if needed.
     pub fn get_mut_entity(&mut self, id: &str) -> Option<&mut Entity> {
-        self.loaded_entities
-            .iter_mut()
-            .find(|entity| entity.id() == id)
+        self.load_entity(id)
     }
 
+    /// Entities loaded so far, not every entity on disk.
     pub fn get_all_entities(&self) -> &Vec<Entity> {
         &self.loaded_entities
     }
 
-    pub fn resolvers(&self) -> &Vec<LLMResolver> {
-        &self.resolvers
+    pub fn resolvers(&self) -> Vec<LLMResolver> {
+        self.shared.resolvers()
     }
 
+    pub fn all_resolvers(&self) -> Vec<LLMResolver> {
+        self.shared.all_resolvers()
+    }
+
+    /// Replaces the resolver pool for this manager and every entity sharing it.
     pub fn set_resolvers(&mut self, resolvers: Vec<LLMResolver>) {
-        self.resolvers = resolvers;
+        self.shared.set_resolvers(resolvers);
+    }
+
+    /// Re-reads the resolvers file and replaces the pool with it. On error
+    /// the current pool is kept.
+    pub fn reload_resolvers(&self) -> io::Result<usize> {
+        let resolvers = try_load_llm_resolvers_from_toml(&self.config.resolvers_path)?;
+        let resolver_count = resolvers.len();
+
+        self.shared.set_resolvers(resolvers);
+
+        info!(
+            resolvers_path = %self.config.resolvers_path.display(),
+            resolver_count,
+            "life_manager_resolvers_reloaded"
+        );
+
+        Ok(resolver_count)
+    }
+
+    /// Writes `resolvers` to the resolvers file and makes them the pool.
+    pub fn save_resolvers(&self, resolvers: Vec<LLMResolver>) -> io::Result<()> {
+        save_llm_resolvers_to_toml(&self.config.resolvers_path, &resolvers)?;
+
+        info!(
+            resolvers_path = %self.config.resolvers_path.display(),
+            resolver_count = resolvers.len(),
+            "life_manager_resolvers_saved"
+        );
+
+        self.shared.set_resolvers(resolvers);
+
+        Ok(())
+    }
+}
+
+/// True if `id` names a single folder, with no separators or `..`, other
+/// than the response cache's.
+pub(crate) fn is_safe_id(id: &str) -> bool {
+    let mut components = Path::new(id).components();
+
+    id != RESPONSE_CACHE_FOLDER
+        && matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::system::unit_next::CognitiveUnitPair;
+
+    /// A manager over an empty root under the temp folder, and that root.
+    pub(crate) fn temp_manager(name: &str) -> (LifeManager, PathBuf) {
+        let root = std::env::temp_dir().join(format!("llmca-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        let resolvers_path = root.join("resolvers.toml");
+        std::fs::write(&resolvers_path, "resolvers = []\n").unwrap();
+
+        let config = LifeManagerConfig::default()
+            .with_root_folder(root.join("life"))
+            .with_resolvers_path(resolvers_path)
+            .with_load_entities(false)
+            .with_response_cache_size(0);
+
+        (LifeManager::with_config(config).unwrap(), root)
+    }
+
+    #[test]
+    fn the_response_cache_is_not_an_entity() {
+        let (manager, root) = temp_manager("response-cache-folder");
+        std::fs::create_dir_all(manager.root_folder().join(RESPONSE_CACHE_FOLDER)).unwrap();
+        manager.try_register_entity("e".to_string()).unwrap();
+
+        assert_eq!(manager.list_entities(), ["e"]);
+
+        let err = manager
+            .try_register_entity(RESPONSE_CACHE_FOLDER.to_string())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    pub(crate) fn spec(state: &str) -> EntitySpec {
+        EntitySpec {
+            size: (2, 2),
+            memory_size: 2,
+            initial_states: vec![CognitiveUnitPair {
+                rule: "keep".to_string(),
+                state: state.to_string(),
+            }],
+            topology: Default::default(),
+            resolvers: None,
+            assignment: Default::default(),
+            species: None,
+            cache_responses: false,
+        }
     }
 }
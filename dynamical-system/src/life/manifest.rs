@@ -0,0 +1,190 @@
+use std::{io, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::system::{
+    assignment::AssignmentStrategy,
+    space::{lattice_index, LLMResolver, LatticeTopology},
+    unit_next::CognitiveUnitPair,
+};
+
+use super::storage::write_atomic;
+
+pub const MANIFEST_FILE: &str = "entity.json";
+/// Largest lattice an entity may have, as width times height.
+pub const MAX_UNITS: usize = 256 * 256;
+/// Longest memory a unit may keep.
+pub const MAX_MEMORY_SIZE: usize = 1024;
+
+/// Everything needed to build a new entity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntitySpec {
+    pub size: (usize, usize),
+    pub memory_size: usize,
+    /// Assigned to cells cyclically, in lattice order.
+    pub initial_states: Vec<CognitiveUnitPair>,
+    #[serde(default)]
+    pub topology: LatticeTopology,
+    /// Ids of the resolvers this entity may use; all of the manager's
+    /// resolvers when unset.
+    #[serde(default)]
+    pub resolvers: Option<Vec<String>>,
+    /// How units are spread over those resolvers.
+    #[serde(default)]
+    pub assignment: AssignmentStrategy,
+    /// Gives units a resolver of their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub species: Option<SpeciesSpec>,
+    /// Whether answers may be reused from the response cache. Off unless
+    /// asked for, since reuse takes away the diversity of repeated answers.
+    #[serde(default)]
+    pub cache_responses: bool,
+}
+
+/// Which resolver each unit belongs to, for experiments where the model is a
+/// property of the unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeciesSpec {
+    /// Resolver ids, laid out over the lattice by `layout`.
+    pub resolvers: Vec<String>,
+    #[serde(default)]
+    pub layout: SpeciesLayout,
+    /// Whether a unit that adopts a neighbor's rule also takes its species.
+    #[serde(default)]
+    pub inherit: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeciesLayout {
+    /// Equal contiguous bands, in lattice order.
+    #[default]
+    Bands,
+    /// Cyclically, in lattice order, like the initial states.
+    Cyclic,
+}
+
+impl SpeciesSpec {
+    /// The species of the unit at `position` of a lattice of `size`.
+    pub fn species_at(&self, position: (usize, usize), size: (usize, usize)) -> &str {
+        let index = lattice_index(position, size);
+        let count = self.resolvers.len();
+
+        let species = match self.layout {
+            SpeciesLayout::Bands => index * count / (size.0 * size.1).max(1),
+            SpeciesLayout::Cyclic => index % count,
+        };
+
+        &self.resolvers[species.min(count - 1)]
+    }
+}
+
+impl EntitySpec {
+    pub fn validate(&self, available: &[LLMResolver]) -> io::Result<()> {
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+
+        if self.size.0 == 0 || self.size.1 == 0 {
+            return invalid(format!("size must be positive, got {:?}", self.size));
+        }
+
+        if self
+            .size
+            .0
+            .checked_mul(self.size.1)
+            .is_none_or(|units| units > MAX_UNITS)
+        {
+            return invalid(format!(
+                "size {:?} has more than {MAX_UNITS} units",
+                self.size
+            ));
+        }
+
+        if self.memory_size == 0 {
+            return invalid("memory_size must be positive".to_string());
+        }
+
+        if self.memory_size > MAX_MEMORY_SIZE {
+            return invalid(format!("memory_size must be at most {MAX_MEMORY_SIZE}"));
+        }
+
+        if self.initial_states.is_empty() {
+            return invalid("at least one initial state is required".to_string());
+        }
+
+        if let Some(resolvers) = &self.resolvers {
+            if resolvers.is_empty() {
+                return invalid("resolver selection must not be empty".to_string());
+            }
+
+            if let Some(unknown) = resolvers.iter().find(|id| {
+                !available
+                    .iter()
+                    .any(|resolver| resolver.id() == id.as_str())
+            }) {
+                return invalid(format!("unknown resolver {unknown}"));
+            }
+        }
+
+        if let Some(species) = &self.species {
+            if species.resolvers.is_empty() {
+                return invalid("species must name at least one resolver".to_string());
+            }
+
+            let selectable = |id: &str| {
+                available.iter().any(|resolver| resolver.id() == id)
+                    && self
+                        .resolvers
+                        .as_ref()
+                        .is_none_or(|selected| selected.iter().any(|selected| selected == id))
+            };
+
+            if let Some(unknown) = species.resolvers.iter().find(|id| !selectable(id)) {
+                return invalid(format!("species resolver {unknown} is not available"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Persisted next to the step snapshots as `entity.json`. Entities created
+/// before manifests existed have none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityManifest {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub spec: EntitySpec,
+}
+
+impl EntityManifest {
+    pub fn load(folder: &Path) -> io::Result<Option<Self>> {
+        let path = folder.join(MANIFEST_FILE);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let json = std::fs::read_to_string(path)?;
+
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    pub fn save(&self, folder: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+
+        write_atomic(&folder.join(MANIFEST_FILE), &json)
+    }
+
+    /// Narrows `resolvers` to this entity's selection, if it has one.
+    pub fn select_resolvers(&self, resolvers: Vec<LLMResolver>) -> Vec<LLMResolver> {
+        match &self.spec.resolvers {
+            Some(selected) => resolvers
+                .into_iter()
+                .filter(|resolver| selected.iter().any(|id| id == resolver.id()))
+                .collect(),
+            None => resolvers,
+        }
+    }
+}
@@ -1,2 +1,11 @@
+pub mod bundle;
+pub mod cache;
+pub mod config;
 pub mod entity;
+pub mod events;
+pub mod intervention;
 pub mod manager;
+pub mod manifest;
+pub mod run;
+pub mod storage;
+pub mod usage;
@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::system::{space::CognitiveSpaceWithMemory, telemetry::StepTelemetry};
+
+/// How many steps a background run may compute and when it should stop early.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunSpec {
+    pub max_steps: u32,
+    #[serde(default)]
+    pub stop_when: StopConditions,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StopConditions {
+    /// Stop once a step leaves every unit's rule and state unchanged.
+    #[serde(default)]
+    pub fixed_point: bool,
+    /// Stop once fewer than this many distinct states remain.
+    #[serde(default)]
+    pub unique_states_below: Option<usize>,
+    /// Stop once the run has spent at least this many tokens.
+    #[serde(default)]
+    pub token_budget: Option<u64>,
+    /// Stop once the run has spent at least this much, in the currency of the
+    /// resolvers' price tables.
+    #[serde(default)]
+    pub cost_budget: Option<f64>,
+    /// Stop once the run has been running for this long, by the wall clock.
+    /// Time spent paused does not count.
+    #[serde(default)]
+    pub max_duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    StepsCompleted,
+    FixedPoint,
+    UniqueStatesBelow,
+    TokenBudgetExceeded,
+    CostBudgetExceeded,
+    TimeLimit,
+    /// The user who started the run reached their step or token quota.
+    QuotaExceeded,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunState {
+    Running,
+    Paused,
+    Finished,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunStatus {
+    pub id: String,
+    pub entity_id: String,
+    pub spec: RunSpec,
+    pub state: RunState,
+    pub stop_reason: Option<StopReason>,
+    pub error: Option<String>,
+    pub start_step: u32,
+    pub steps_completed: u32,
+    pub tokens_used: u64,
+    #[serde(default)]
+    pub cost_used: f64,
+    /// Wall-clock time spent running, excluding pauses.
+    pub elapsed_ms: u64,
+    /// When the run last started or resumed, while it is not paused.
+    #[serde(skip)]
+    running_since: Option<Instant>,
+    /// Running time before `running_since`.
+    #[serde(skip)]
+    banked: Duration,
+    pub last_telemetry: Option<StepTelemetry>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl RunStatus {
+    pub fn new(id: String, entity_id: String, spec: RunSpec, start_step: u32) -> Self {
+        Self {
+            id,
+            entity_id,
+            spec,
+            state: RunState::Running,
+            stop_reason: None,
+            error: None,
+            start_step,
+            steps_completed: 0,
+            tokens_used: 0,
+            cost_used: 0.0,
+            elapsed_ms: 0,
+            running_since: Some(Instant::now()),
+            banked: Duration::ZERO,
+            last_telemetry: None,
+            started_at: Utc::now(),
+            finished_at: None,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, RunState::Finished | RunState::Failed)
+    }
+
+    /// Accounts for a computed step and returns why the run should stop, if it
+    /// should. `fixed_point` tells whether the step changed nothing.
+    pub fn record_step(
+        &mut self,
+        telemetry: StepTelemetry,
+        fixed_point: bool,
+    ) -> Option<StopReason> {
+        self.steps_completed += 1;
+        self.tokens_used += telemetry.total_tokens;
+        self.cost_used += telemetry.cost;
+        self.tick();
+
+        let unique_states = telemetry.unique_states;
+        self.last_telemetry = Some(telemetry);
+
+        let stop_when = &self.spec.stop_when;
+
+        if stop_when.fixed_point && fixed_point {
+            Some(StopReason::FixedPoint)
+        } else if stop_when
+            .unique_states_below
+            .is_some_and(|k| unique_states < k)
+        {
+            Some(StopReason::UniqueStatesBelow)
+        } else if stop_when
+            .token_budget
+            .is_some_and(|budget| self.tokens_used >= budget)
+        {
+            Some(StopReason::TokenBudgetExceeded)
+        } else if stop_when
+            .cost_budget
+            .is_some_and(|budget| self.cost_used >= budget)
+        {
+            Some(StopReason::CostBudgetExceeded)
+        } else if stop_when
+            .max_duration_secs
+            .is_some_and(|secs| self.elapsed_ms >= secs * 1000)
+        {
+            Some(StopReason::TimeLimit)
+        } else if self.steps_completed >= self.spec.max_steps {
+            Some(StopReason::StepsCompleted)
+        } else {
+            None
+        }
+    }
+
+    /// Stops the clock while the run is paused.
+    pub fn stop_clock(&mut self) {
+        if let Some(since) = self.running_since.take() {
+            self.banked += since.elapsed();
+        }
+
+        self.tick();
+    }
+
+    /// Restarts the clock when the run resumes.
+    pub fn start_clock(&mut self) {
+        self.running_since.get_or_insert_with(Instant::now);
+    }
+
+    /// Brings `elapsed_ms` up to date with the clock.
+    pub fn tick(&mut self) {
+        let running = self.banked + self.running_since.map_or(Duration::ZERO, |s| s.elapsed());
+
+        self.elapsed_ms = running.as_millis() as u64;
+    }
+
+    pub fn finish(&mut self, stop_reason: StopReason) {
+        self.stop_clock();
+        self.state = RunState::Finished;
+        self.stop_reason = Some(stop_reason);
+        self.finished_at = Some(Utc::now());
+    }
+
+    pub fn fail(&mut self, error: String) {
+        self.stop_clock();
+        self.state = RunState::Failed;
+        self.error = Some(error);
+        self.finished_at = Some(Utc::now());
+    }
+}
+
+/// Every unit's current rule and state, used to detect fixed points.
+pub fn lattice_signature(
+    space: &CognitiveSpaceWithMemory,
+) -> BTreeMap<(usize, usize), (String, String)> {
+    space
+        .get_units()
+        .into_iter()
+        .filter_map(|unit| {
+            let last = unit.memory.last()?;
+
+            Some((unit.position, (last.rule.clone(), last.state.clone())))
+        })
+        .collect()
+}
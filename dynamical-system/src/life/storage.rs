@@ -0,0 +1,225 @@
+use std::{
+    collections::HashMap,
+    fs::{read_dir, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_derive::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::system::{
+    space::StepObserver, telemetry::StepTelemetry, unit_next::CognitiveUnitComplex,
+};
+
+pub const QUARANTINE_FOLDER: &str = "quarantine";
+
+const TMP_EXTENSION: &str = "tmp";
+
+pub const INTERVENTION_SIDECAR: &str = "intervention";
+pub const TELEMETRY_SIDECAR: &str = "telemetry";
+
+/// Optional files saved next to a step snapshot as `<step>.<sidecar>.json`.
+/// They move, archive and export together with their step.
+const STEP_SIDECARS: [&str; 2] = [INTERVENTION_SIDECAR, TELEMETRY_SIDECAR];
+
+/// Writes `content` to a sibling temp file, syncs it and renames it over
+/// `path`, so readers see either the old file or the complete new one. The
+/// folder is synced as well so the rename survives a crash.
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let tmp_path = tmp_path_for(path);
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+
+    std::fs::rename(&tmp_path, path)?;
+
+    sync_parent(path)
+}
+
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        Some(parent) => File::open(parent)?.sync_all(),
+        None => File::open(".")?.sync_all(),
+    }
+}
+
+/// Folders cannot be opened for syncing outside Unix.
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Removes temp files left behind by an interrupted [`write_atomic`].
+pub fn remove_stale_tmp_files(folder: &Path) -> io::Result<usize> {
+    let mut removed = 0;
+
+    for entry in read_dir(folder)? {
+        let path = entry?.path();
+
+        if path.extension().and_then(|s| s.to_str()) == Some(TMP_EXTENSION) {
+            std::fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Moves an unreadable file into `<folder>/quarantine/` so it stops blocking
+/// startup but can still be inspected.
+pub fn quarantine(folder: &Path, file: &Path) -> io::Result<PathBuf> {
+    let quarantine_folder = folder.join(QUARANTINE_FOLDER);
+    std::fs::create_dir_all(&quarantine_folder)?;
+
+    let target = quarantine_folder.join(file.file_name().unwrap_or_default());
+    std::fs::rename(file, &target)?;
+
+    Ok(target)
+}
+
+pub fn sidecar_path(folder: &Path, step: u32, sidecar: &str) -> PathBuf {
+    folder.join(format!("{}.{}.json", step, sidecar))
+}
+
+/// File names of the snapshot of `step` and of the sidecars it has in
+/// `folder`.
+pub fn step_file_names(folder: &Path, step: u32) -> Vec<String> {
+    let mut names = vec![format!("{}.json", step)];
+
+    names.extend(
+        STEP_SIDECARS
+            .iter()
+            .map(|sidecar| format!("{}.{}.json", step, sidecar))
+            .filter(|name| folder.join(name).is_file()),
+    );
+
+    names
+}
+
+/// Moves the snapshot of `step` and its sidecars from one folder to another.
+pub fn move_step_files(from: &Path, to: &Path, step: u32) -> io::Result<()> {
+    for name in step_file_names(from, step) {
+        std::fs::rename(from.join(&name), to.join(name))?;
+    }
+
+    Ok(())
+}
+
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{TMP_EXTENSION}"));
+
+    path.with_file_name(file_name)
+}
+
+/// Unit results of a step that has not finished yet, persisted after every
+/// chunk so a crash only loses the chunk that was in flight.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepCheckpoint {
+    pub base_step: u32,
+    pub units: Vec<CheckpointUnit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointUnit {
+    pub position: (usize, usize),
+    pub result: CognitiveUnitComplex,
+}
+
+impl StepCheckpoint {
+    pub fn path(folder: &Path, step: u32) -> PathBuf {
+        folder.join(format!("{}.partial.json", step))
+    }
+
+    /// Loads the checkpoint for `base_step + 1`, discarding it if it belongs
+    /// to a different base step (e.g. after a rewind) or cannot be parsed.
+    pub fn load(folder: &Path, base_step: u32) -> Self {
+        let path = Self::path(folder, base_step + 1);
+        let empty = Self {
+            base_step,
+            units: vec![],
+        };
+
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            return empty;
+        };
+
+        match serde_json::from_str::<Self>(&json) {
+            Ok(checkpoint) if checkpoint.base_step == base_step => {
+                debug!(
+                    base_step,
+                    units = checkpoint.units.len(),
+                    "step_checkpoint_loaded"
+                );
+
+                checkpoint
+            }
+            Ok(checkpoint) => {
+                warn!(
+                    base_step,
+                    checkpoint_base_step = checkpoint.base_step,
+                    "step_checkpoint_discarded_stale"
+                );
+
+                empty
+            }
+            Err(err) => {
+                warn!(base_step, error = %err, "step_checkpoint_discarded_corrupt");
+
+                empty
+            }
+        }
+    }
+
+    pub fn completed(&self) -> HashMap<(usize, usize), CognitiveUnitComplex> {
+        self.units
+            .iter()
+            .map(|unit| (unit.position, unit.result.clone()))
+            .collect()
+    }
+
+    pub fn remove(folder: &Path, step: u32) {
+        let _ = std::fs::remove_file(Self::path(folder, step));
+    }
+}
+
+/// [`StepObserver`] that appends every completed unit to the checkpoint and
+/// rewrites it atomically once per chunk.
+pub struct CheckpointWriter {
+    path: PathBuf,
+    checkpoint: StepCheckpoint,
+}
+
+impl CheckpointWriter {
+    pub fn new(folder: &Path, checkpoint: StepCheckpoint) -> Self {
+        Self {
+            path: StepCheckpoint::path(folder, checkpoint.base_step + 1),
+            checkpoint,
+        }
+    }
+}
+
+impl StepObserver for CheckpointWriter {
+    fn unit_completed(&mut self, position: (usize, usize), unit: &CognitiveUnitComplex) {
+        self.checkpoint.units.push(CheckpointUnit {
+            position,
+            result: unit.clone(),
+        });
+    }
+
+    fn chunk_completed(&mut self, _telemetry: &StepTelemetry) {
+        let result = serde_json::to_vec(&self.checkpoint)
+            .map_err(io::Error::from)
+            .and_then(|json| write_atomic(&self.path, &json));
+
+        if let Err(err) = result {
+            warn!(path = %self.path.display(), error = %err, "step_checkpoint_write_failed");
+        }
+    }
+}
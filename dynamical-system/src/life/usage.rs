@@ -0,0 +1,58 @@
+use std::{collections::BTreeMap, io, path::Path};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::system::telemetry::{ResolverUsage, StepTelemetry};
+
+use super::storage::write_atomic;
+
+pub const USAGE_FILE: &str = "usage.json";
+
+/// Everything an entity has spent computing steps, persisted as `usage.json`.
+/// Rewinding refunds nothing: the tokens of discarded steps were still spent.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntityUsage {
+    pub steps: u32,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    #[serde(default)]
+    pub resolvers: BTreeMap<String, ResolverUsage>,
+}
+
+impl EntityUsage {
+    /// The saved usage, or none spent if the entity predates usage tracking.
+    pub fn load(folder: &Path) -> io::Result<Self> {
+        let path = folder.join(USAGE_FILE);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let json = std::fs::read_to_string(path)?;
+
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self, folder: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+
+        write_atomic(&folder.join(USAGE_FILE), &json)
+    }
+
+    pub fn record(&mut self, telemetry: &StepTelemetry) {
+        self.steps += 1;
+        self.input_tokens += telemetry.input_tokens;
+        self.output_tokens += telemetry.output_tokens;
+        self.total_tokens += telemetry.total_tokens;
+        self.cost += telemetry.cost;
+
+        for (resolver_id, usage) in &telemetry.resolvers {
+            self.resolvers
+                .entry(resolver_id.clone())
+                .or_default()
+                .add(usage);
+        }
+    }
+}
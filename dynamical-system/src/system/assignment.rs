@@ -0,0 +1,282 @@
+//! How the units of a step are spread over the resolvers whose circuit
+//! breaker is closed.
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::space::{lattice_index, LLMResolver};
+
+/// Least share of its capacity a resolver keeps under adaptive assignment,
+/// so one whose calls all failed still gets units to recover on.
+const MIN_ADAPTIVE_SHARE: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssignmentStrategy {
+    /// Each resolver in turn, whatever its capacity.
+    #[default]
+    RoundRobin,
+    /// In proportion to the resolvers' capacity.
+    Weighted,
+    /// A unit keeps the same resolver from step to step while the pool does
+    /// not change; units are spread in proportion to capacity.
+    Sticky,
+    /// The lattice is cut into contiguous bands, in lattice order, one per
+    /// resolver and sized by capacity.
+    Region,
+    /// In proportion to capacity scaled by the throughput observed so far.
+    Adaptive,
+}
+
+/// Picks the resolver of each unit of one step.
+pub(crate) struct Assigner {
+    strategy: AssignmentStrategy,
+    size: (usize, usize),
+    units: usize,
+    /// Running credit of the smooth weighted round-robin, by resolver index.
+    credit: Vec<f64>,
+}
+
+impl Assigner {
+    pub fn new(
+        strategy: AssignmentStrategy,
+        positions: &[(usize, usize)],
+        resolver_count: usize,
+    ) -> Self {
+        Self {
+            strategy,
+            size: (
+                positions.iter().map(|(x, _)| x + 1).max().unwrap_or(1),
+                positions.iter().map(|(_, y)| y + 1).max().unwrap_or(1),
+            ),
+            units: positions.len(),
+            credit: vec![0.0; resolver_count],
+        }
+    }
+
+    /// Index in `resolvers` of the resolver for the unit at `position`, the
+    /// `nth` of its chunk, among the indices in `active`.
+    pub fn assign(
+        &mut self,
+        resolvers: &[LLMResolver],
+        active: &[usize],
+        position: (usize, usize),
+        nth: usize,
+    ) -> usize {
+        match self.strategy {
+            AssignmentStrategy::RoundRobin => active[nth % active.len()],
+            AssignmentStrategy::Weighted => {
+                let weights = active
+                    .iter()
+                    .map(|&index| resolvers[index].capacity() as f64)
+                    .collect::<Vec<_>>();
+
+                self.smooth_weighted(active, &weights)
+            }
+            AssignmentStrategy::Adaptive => {
+                let weights = adaptive_weights(resolvers, active);
+
+                self.smooth_weighted(active, &weights)
+            }
+            AssignmentStrategy::Sticky => rendezvous(resolvers, active, position),
+            AssignmentStrategy::Region => {
+                let region = self.region(resolvers, position);
+
+                if active.contains(&region) {
+                    region
+                } else {
+                    rendezvous(resolvers, active, position)
+                }
+            }
+        }
+    }
+
+    /// Nginx's smooth weighted round-robin: every pick adds each weight to
+    /// its resolver's credit and takes the total back from the richest one.
+    fn smooth_weighted(&mut self, active: &[usize], weights: &[f64]) -> usize {
+        let total = weights.iter().sum::<f64>();
+        let mut picked = 0;
+
+        for (i, &index) in active.iter().enumerate() {
+            self.credit[index] += weights[i];
+
+            if self.credit[index] > self.credit[active[picked]] {
+                picked = i;
+            }
+        }
+
+        self.credit[active[picked]] -= total;
+
+        active[picked]
+    }
+
+    /// The resolver owning the band of the lattice `position` falls in,
+    /// whether its breaker is closed or not, so bands do not move.
+    fn region(&self, resolvers: &[LLMResolver], position: (usize, usize)) -> usize {
+        let total = resolvers
+            .iter()
+            .map(|resolver| resolver.capacity() as usize)
+            .sum::<usize>();
+        let index = lattice_index(position, self.size);
+        let slot = index * total / self.units.max(1);
+
+        let mut end = 0;
+
+        for (i, resolver) in resolvers.iter().enumerate() {
+            end += resolver.capacity() as usize;
+
+            if slot < end {
+                return i;
+            }
+        }
+
+        resolvers.len() - 1
+    }
+}
+
+/// Capacity scaled by each resolver's throughput relative to the mean of the
+/// observed ones, but no less than [`MIN_ADAPTIVE_SHARE`] of it. Resolvers
+/// without observations keep their capacity.
+fn adaptive_weights(resolvers: &[LLMResolver], active: &[usize]) -> Vec<f64> {
+    let throughputs = active
+        .iter()
+        .map(|&index| resolvers[index].guard().throughput())
+        .collect::<Vec<_>>();
+
+    let observed = throughputs.iter().flatten().collect::<Vec<_>>();
+    let mean = observed.iter().copied().sum::<f64>() / observed.len().max(1) as f64;
+
+    active
+        .iter()
+        .zip(throughputs)
+        .map(|(&index, throughput)| {
+            let capacity = resolvers[index].capacity() as f64;
+
+            match throughput {
+                Some(throughput) if mean > 0.0 => {
+                    (capacity * throughput / mean).max(capacity * MIN_ADAPTIVE_SHARE)
+                }
+                _ => capacity,
+            }
+        })
+        .collect()
+}
+
+/// Weighted rendezvous hashing: the same position picks the same resolver as
+/// long as it stays active, and losing a resolver only moves its own units.
+fn rendezvous(resolvers: &[LLMResolver], active: &[usize], position: (usize, usize)) -> usize {
+    let score = |index: usize| {
+        let resolver = &resolvers[index];
+        let digest = md5::compute(format!("{}:{}:{}", resolver.id(), position.0, position.1));
+        let hash = u64::from_le_bytes(digest.0[..8].try_into().unwrap());
+        // Uniform in (0, 1).
+        let uniform = (hash as f64 + 1.0) / (u64::MAX as f64 + 2.0);
+
+        resolver.capacity() as f64 / -uniform.ln()
+    };
+
+    active
+        .iter()
+        .copied()
+        .max_by(|a, b| score(*a).total_cmp(&score(*b)))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver(name: &str, capacity: u32) -> LLMResolver {
+        let mut resolver = LLMResolver::new(
+            Some(name.to_string()),
+            "http://localhost".to_string(),
+            "_".to_string(),
+            "model".to_string(),
+            None,
+        );
+
+        resolver.set_capacity(Some(capacity));
+
+        resolver
+    }
+
+    fn lattice(width: usize, height: usize) -> Vec<(usize, usize)> {
+        (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .collect()
+    }
+
+    #[test]
+    fn smooth_weighted_interleaves_in_proportion() {
+        let mut assigner = Assigner::new(AssignmentStrategy::Weighted, &[], 3);
+        let picks = (0..7)
+            .map(|_| assigner.smooth_weighted(&[0, 1, 2], &[5.0, 1.0, 1.0]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(picks, [0, 0, 1, 0, 2, 0, 0]);
+    }
+
+    #[test]
+    fn smooth_weighted_skips_inactive_resolvers() {
+        let mut assigner = Assigner::new(AssignmentStrategy::Weighted, &[], 3);
+        let picks = (0..4)
+            .map(|_| assigner.smooth_weighted(&[0, 2], &[1.0, 1.0]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(picks, [0, 2, 0, 2]);
+    }
+
+    #[test]
+    fn adaptive_weights_keep_a_floor_for_failing_resolvers() {
+        let resolvers = [resolver("a", 2), resolver("b", 2), resolver("c", 2)];
+
+        resolvers[0].guard().observe(100, true);
+        resolvers[1].guard().observe(100, false);
+
+        let weights = adaptive_weights(&resolvers, &[0, 1, 2]);
+
+        assert_eq!(weights, [4.0, 2.0 * MIN_ADAPTIVE_SHARE, 2.0]);
+    }
+
+    #[test]
+    fn rendezvous_only_moves_the_units_of_a_lost_resolver() {
+        let resolvers = [resolver("a", 1), resolver("b", 1), resolver("c", 1)];
+        let positions = lattice(8, 8);
+
+        let before = positions
+            .iter()
+            .map(|&position| rendezvous(&resolvers, &[0, 1, 2], position))
+            .collect::<Vec<_>>();
+        let again = positions
+            .iter()
+            .map(|&position| rendezvous(&resolvers, &[0, 1, 2], position))
+            .collect::<Vec<_>>();
+        let without_b = positions
+            .iter()
+            .map(|&position| rendezvous(&resolvers, &[0, 2], position))
+            .collect::<Vec<_>>();
+
+        assert_eq!(before, again);
+        assert!(before.contains(&1));
+
+        for (before, after) in before.iter().zip(&without_b) {
+            if *before != 1 {
+                assert_eq!(before, after);
+            }
+        }
+    }
+
+    #[test]
+    fn region_cuts_bands_in_lattice_order_by_capacity() {
+        let resolvers = [resolver("a", 1), resolver("b", 3)];
+        let positions = lattice(4, 2);
+        let assigner = Assigner::new(AssignmentStrategy::Region, &positions, 2);
+
+        let regions = positions
+            .iter()
+            .map(|&position| assigner.region(&resolvers, position))
+            .collect::<Vec<_>>();
+
+        // `lattice` lists positions in lattice order, so bands are contiguous.
+        assert_eq!(regions, [0, 0, 1, 1, 1, 1, 1, 1]);
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::{header, Client};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use super::{space::LLMResolver, unit::LLMProvider, unit_next::normalize_ollama_base_url};
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of asking a resolver's API for its model list. No completion is
+/// requested, so a check spends no tokens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolverHealth {
+    pub resolver_id: String,
+    pub healthy: bool,
+    pub status: Option<u16>,
+    /// Whether the model list contains the resolver's model, when the API
+    /// answered with one.
+    pub model_available: Option<bool>,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    pub checked_at: DateTime<Utc>,
+}
+
+pub async fn check_resolver(resolver: &LLMResolver) -> ResolverHealth {
+    let started_at = Instant::now();
+
+    let url = match resolver.provider() {
+        LLMProvider::Ollama => {
+            format!("{}/api/tags", normalize_ollama_base_url(resolver.api_url()))
+        }
+        LLMProvider::OpenRouter => format!("{}/models", resolver.api_url().trim_end_matches('/')),
+    };
+
+    let mut request = Client::new().get(&url).timeout(HEALTH_CHECK_TIMEOUT);
+
+    let api_key = resolver.api_key().trim();
+
+    if !api_key.is_empty() && api_key != "_" && !api_key.eq_ignore_ascii_case("ollama") {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {api_key}"));
+    }
+
+    let (status, model_available, error) = match request.send().await {
+        Ok(response) => {
+            let status = response.status();
+
+            if status.is_success() {
+                let model_available = response
+                    .json::<Value>()
+                    .await
+                    .ok()
+                    .and_then(|models| lists_model(&models, resolver.model_name()));
+
+                (Some(status.as_u16()), model_available, None)
+            } else {
+                (Some(status.as_u16()), None, Some(format!("HTTP {status}")))
+            }
+        }
+        Err(err) => (None, None, Some(err.to_string())),
+    };
+
+    let health = ResolverHealth {
+        resolver_id: resolver.id().to_string(),
+        healthy: error.is_none() && model_available != Some(false),
+        status,
+        model_available,
+        error,
+        latency_ms: started_at.elapsed().as_millis() as u64,
+        checked_at: Utc::now(),
+    };
+
+    if health.healthy {
+        debug!(
+            resolver_id = %health.resolver_id,
+            latency_ms = health.latency_ms,
+            "resolver_health_checked"
+        );
+    } else {
+        warn!(
+            resolver_id = %health.resolver_id,
+            status = ?health.status,
+            model_available = ?health.model_available,
+            error = health.error.as_deref().unwrap_or_default(),
+            latency_ms = health.latency_ms,
+            "resolver_unhealthy"
+        );
+    }
+
+    health
+}
+
+/// Looks for `model_name` in an Ollama `models[].name` or OpenRouter
+/// `data[].id` listing. Ollama names without a tag mean `:latest`.
+fn lists_model(models: &Value, model_name: &str) -> Option<bool> {
+    let names = models
+        .get("models")
+        .and_then(Value::as_array)
+        .map(|models| (models, "name"))
+        .or_else(|| {
+            models
+                .get("data")
+                .and_then(Value::as_array)
+                .map(|models| (models, "id"))
+        })
+        .map(|(models, key)| {
+            models
+                .iter()
+                .filter_map(|model| model.get(key).and_then(Value::as_str))
+                .collect::<Vec<_>>()
+        })?;
+
+    Some(
+        names
+            .iter()
+            .any(|name| *name == model_name || name.strip_suffix(":latest") == Some(model_name)),
+    )
+}
@@ -0,0 +1,128 @@
+//! Prometheus metrics derived from [`StepTelemetry`]. Recording goes through
+//! the `metrics` facade and is a no-op until a binary installs a recorder;
+//! with the `prometheus` feature this module also provides one.
+
+use metrics::{counter, gauge, histogram, Label};
+
+use super::telemetry::StepTelemetry;
+
+/// Address of the standalone exporter, e.g. `0.0.0.0:9000`, for binaries that
+/// do not serve `/metrics` themselves.
+pub const METRICS_ADDR_ENV: &str = "LLMCA_METRICS_ADDR";
+
+/// Publishes a computed step of `entity_id`: step and unit counts, failures,
+/// tokens, cost and resolver latency, labelled by entity and, where it
+/// applies, resolver.
+pub fn record_step(entity_id: &str, telemetry: &StepTelemetry) {
+    let entity = entity_id.to_string();
+
+    counter!("llmca_steps_total", "entity" => entity.clone()).increment(1);
+    histogram!("llmca_step_duration_seconds", "entity" => entity.clone())
+        .record(telemetry.elapsed_ms as f64 / 1000.0);
+    gauge!("llmca_unique_states", "entity" => entity.clone()).set(telemetry.unique_states as f64);
+    counter!("llmca_response_cache_total", "entity" => entity.clone(), "result" => "hit")
+        .increment(telemetry.cache_hits as u64);
+    counter!("llmca_response_cache_total", "entity" => entity.clone(), "result" => "miss")
+        .increment(telemetry.cache_misses as u64);
+
+    for (resolver_id, usage) in &telemetry.resolvers {
+        let labels = resolver_labels(&entity, resolver_id);
+
+        counter!("llmca_units_completed_total", labels.clone()).increment(usage.units as u64);
+        counter!("llmca_tokens_total", with(&labels, "direction", "input"))
+            .increment(usage.input_tokens);
+        counter!("llmca_tokens_total", with(&labels, "direction", "output"))
+            .increment(usage.output_tokens);
+        // Costs are fractional, which counters are not.
+        gauge!("llmca_cost", labels).increment(usage.cost);
+    }
+
+    for (resolver_id, stats) in &telemetry.resolver_stats {
+        let labels = resolver_labels(&entity, resolver_id);
+
+        counter!(
+            "llmca_llm_failures_total",
+            with(&labels, "class", "transport")
+        )
+        .increment(stats.transport_failures as u64);
+        counter!(
+            "llmca_llm_failures_total",
+            with(&labels, "class", "timeout")
+        )
+        .increment(stats.timeouts as u64);
+        counter!(
+            "llmca_parse_failures_total",
+            with(&labels, "class", "schema_violation")
+        )
+        .increment(stats.structured_output_failures as u64);
+        counter!(
+            "llmca_parse_failures_total",
+            with(&labels, "class", "validation_rejected")
+        )
+        .increment(stats.validation_rejections as u64);
+        counter!("llmca_retried_ok_total", labels.clone()).increment(stats.retried_ok as u64);
+
+        let latency = histogram!("llmca_resolver_latency_seconds", labels);
+
+        for latency_ms in stats.latencies_ms() {
+            latency.record(*latency_ms as f64 / 1000.0);
+        }
+    }
+}
+
+fn resolver_labels(entity: &str, resolver_id: &str) -> Vec<Label> {
+    vec![
+        Label::new("entity", entity.to_string()),
+        Label::new("resolver", resolver_id.to_string()),
+    ]
+}
+
+fn with(labels: &[Label], key: &'static str, value: &'static str) -> Vec<Label> {
+    let mut labels = labels.to_vec();
+    labels.push(Label::new(key, value));
+    labels
+}
+
+#[cfg(feature = "prometheus")]
+pub use exporter::{install_recorder, serve_exporter};
+
+#[cfg(feature = "prometheus")]
+mod exporter {
+    use std::{net::SocketAddr, time::Duration};
+
+    use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+
+    const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+    const LATENCY_BUCKETS: &[f64] = &[
+        0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+    ];
+
+    fn builder() -> Result<PrometheusBuilder, BuildError> {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)
+    }
+
+    /// Installs the global recorder and returns a handle that renders it, for
+    /// servers that expose `/metrics` themselves. Must be called inside a
+    /// Tokio runtime, which runs the recorder's periodic upkeep.
+    pub fn install_recorder() -> Result<PrometheusHandle, BuildError> {
+        let handle = builder()?.install_recorder()?;
+        let upkeep = handle.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(UPKEEP_INTERVAL).await;
+                upkeep.run_upkeep();
+            }
+        });
+
+        Ok(handle)
+    }
+
+    /// Installs the global recorder behind its own HTTP listener on `addr`,
+    /// which answers scrapes on any path.
+    pub fn serve_exporter(addr: SocketAddr) -> Result<(), BuildError> {
+        builder()?.with_http_listener(addr).install()
+    }
+}
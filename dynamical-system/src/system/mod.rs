@@ -1,4 +1,11 @@
 pub mod api;
+pub mod assignment;
+pub mod health;
+pub mod metrics;
+pub mod observability;
+pub mod policy;
+pub mod render;
+pub mod response_cache;
 pub mod space;
 pub mod telemetry;
 pub mod unit;
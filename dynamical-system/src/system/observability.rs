@@ -0,0 +1,126 @@
+//! Tracing setup shared by the binaries: `RUST_LOG` filtering, logs on
+//! stdout and, with the `otlp` feature, span export to an OpenTelemetry
+//! collector.
+
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Collector base URL, e.g. `http://localhost:4318`. Spans are exported over
+/// OTLP/HTTP to `<endpoint>/v1/traces` only when this is set.
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Json,
+    Pretty,
+}
+
+/// Flushes the spans still waiting for export when dropped. Keep it alive for
+/// as long as the program runs.
+#[must_use]
+pub struct TracingGuard {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(err) = provider.shutdown() {
+                eprintln!("failed to flush spans: {err}");
+            }
+        }
+    }
+}
+
+/// Installs the global subscriber. `RUST_LOG` filters both the logs and the
+/// exported spans and defaults to `info`.
+#[cfg_attr(not(feature = "otlp"), allow(unused_variables))]
+pub fn init_tracing(service_name: &'static str, format: LogFormat) -> TracingGuard {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let logs = match format {
+        LogFormat::Json => fmt::layer().with_target(true).json().boxed(),
+        LogFormat::Pretty => fmt::layer().with_target(true).pretty().boxed(),
+    };
+
+    #[cfg(feature = "otlp")]
+    let (spans, provider) = match otlp::tracer_provider(service_name) {
+        Some(provider) => (Some(otlp::layer(&provider, service_name)), Some(provider)),
+        None => (None, None),
+    };
+
+    #[cfg(not(feature = "otlp"))]
+    let spans: Option<Box<dyn Layer<_> + Send + Sync>> = None;
+
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(logs)
+        .with(spans)
+        .try_init();
+
+    #[cfg(feature = "otlp")]
+    if provider.is_some() {
+        tracing::info!(
+            service_name,
+            endpoint = std::env::var(OTLP_ENDPOINT_ENV).unwrap_or_default(),
+            "otlp_export_enabled"
+        );
+    }
+
+    TracingGuard {
+        #[cfg(feature = "otlp")]
+        provider,
+    }
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+    use tracing::Subscriber;
+    use tracing_subscriber::{registry::LookupSpan, Layer};
+
+    use super::OTLP_ENDPOINT_ENV;
+
+    /// A provider batching spans to the collector, or `None` if no collector
+    /// is configured or the exporter cannot be built.
+    pub fn tracer_provider(service_name: &'static str) -> Option<SdkTracerProvider> {
+        let endpoint = std::env::var(OTLP_ENDPOINT_ENV).ok()?;
+
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+            .build();
+
+        let exporter = match exporter {
+            Ok(exporter) => exporter,
+            Err(err) => {
+                eprintln!("failed to build the OTLP exporter for {endpoint}: {err}");
+                return None;
+            }
+        };
+
+        let resource = Resource::builder().with_service_name(service_name).build();
+
+        Some(
+            SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(resource)
+                .build(),
+        )
+    }
+
+    pub fn layer<S>(
+        provider: &SdkTracerProvider,
+        service_name: &'static str,
+    ) -> Box<dyn Layer<S> + Send + Sync>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span> + Send + Sync,
+    {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(service_name))
+            .boxed()
+    }
+}
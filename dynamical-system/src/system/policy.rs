@@ -0,0 +1,475 @@
+//! Per-resolver request policies: timeout, retries with exponential backoff,
+//! rate limits and a circuit breaker. The policy is read from the resolvers
+//! file; the state it needs at runtime lives in a [`ResolverGuard`] shared by
+//! every copy of the resolver.
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde_derive::{Deserialize, Serialize};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Weight of the latest call in the throughput averages.
+const THROUGHPUT_SMOOTHING: f64 = 0.2;
+const DEFAULT_BACKOFF_MS: u64 = 500;
+/// Longest circuit breaker cooldown a policy may ask for.
+const MAX_COOLDOWN_SECS: u64 = 365 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolverPolicy {
+    /// Seconds a single request may take before it counts as a timeout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<f64>,
+    /// How many times a transport error or timeout is retried.
+    #[serde(default)]
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every following one.
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<CircuitBreakerPolicy>,
+    /// Resolvers a unit that failed here is retried on, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failover: Vec<String>,
+    /// Milliseconds after which a unit still waiting for this resolver is
+    /// also sent to the first available resolver of `failover`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hedge_after_ms: Option<u64>,
+}
+
+/// Takes a resolver out of the rotation after `failures` consecutive units
+/// failed on it with a transport error or timeout. After `cooldown_secs` it
+/// is tried again, and the first failure takes it out once more.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CircuitBreakerPolicy {
+    pub failures: u32,
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+impl Default for ResolverPolicy {
+    fn default() -> Self {
+        Self {
+            timeout_secs: None,
+            retries: 0,
+            backoff_ms: DEFAULT_BACKOFF_MS,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            circuit_breaker: None,
+            failover: vec![],
+            hedge_after_ms: None,
+        }
+    }
+}
+
+fn default_backoff_ms() -> u64 {
+    DEFAULT_BACKOFF_MS
+}
+
+fn default_cooldown_secs() -> u64 {
+    CircuitBreakerPolicy::DEFAULT_COOLDOWN_SECS
+}
+
+impl CircuitBreakerPolicy {
+    pub const DEFAULT_COOLDOWN_SECS: u64 = 60;
+}
+
+impl ResolverPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Checks the values a [`Duration`] is built from; a timeout must be a
+    /// finite, non-negative number of seconds and a cooldown at most a year.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(circuit_breaker) = self.circuit_breaker {
+            if circuit_breaker.cooldown_secs > MAX_COOLDOWN_SECS {
+                return Err(format!(
+                    "cooldown_secs must be at most {MAX_COOLDOWN_SECS}, got {}",
+                    circuit_breaker.cooldown_secs
+                ));
+            }
+        }
+
+        if let Some(secs) = self.timeout_secs {
+            if Duration::try_from_secs_f64(secs).is_err() {
+                return Err(format!(
+                    "timeout_secs must be a finite, non-negative number, got {secs}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The request timeout; zero and values [`ResolverPolicy::validate`]
+    /// rejects mean none.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_secs
+            .filter(|secs| *secs > 0.0)
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    }
+
+    pub fn hedge_after(&self) -> Option<Duration> {
+        self.hedge_after_ms.map(Duration::from_millis)
+    }
+
+    /// Wait before retry number `attempt`, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor))
+    }
+}
+
+/// Runtime state behind a resolver's policy: the requests and tokens of the
+/// last minute and the circuit breaker, along with the throughput observed
+/// for adaptive assignment.
+#[derive(Debug, Default)]
+pub struct ResolverGuard {
+    window: Mutex<RateWindow>,
+    breaker: Mutex<BreakerState>,
+    throughput: Mutex<Option<Throughput>>,
+}
+
+/// Moving averages of a resolver's latency and success rate.
+#[derive(Debug, Clone, Copy)]
+struct Throughput {
+    latency_ms: f64,
+    success_rate: f64,
+}
+
+#[derive(Debug, Default)]
+struct RateWindow {
+    requests: VecDeque<Instant>,
+    tokens: VecDeque<(Instant, u64)>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open: BreakerOpen,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+enum BreakerOpen {
+    #[default]
+    Closed,
+    Until(Instant),
+    /// The cooldown ends past what an [`Instant`] can hold.
+    ForGood,
+}
+
+impl BreakerOpen {
+    fn is_open(self, now: Instant) -> bool {
+        match self {
+            BreakerOpen::Closed => false,
+            BreakerOpen::Until(until) => now < until,
+            BreakerOpen::ForGood => true,
+        }
+    }
+}
+
+impl RateWindow {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .requests
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= RATE_WINDOW)
+        {
+            self.requests.pop_front();
+        }
+
+        while self
+            .tokens
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= RATE_WINDOW)
+        {
+            self.tokens.pop_front();
+        }
+    }
+
+    /// How long until a request fits in the limits, zero if it does now.
+    fn wait(&self, policy: &ResolverPolicy, now: Instant) -> Duration {
+        let until_expired = |at: Instant| RATE_WINDOW.saturating_sub(now.duration_since(at));
+        let mut wait = Duration::ZERO;
+
+        if let Some(limit) = policy.requests_per_minute.filter(|limit| *limit > 0) {
+            let excess = (self.requests.len() + 1).saturating_sub(limit as usize);
+
+            if excess > 0 {
+                wait = wait.max(until_expired(self.requests[excess - 1]));
+            }
+        }
+
+        if let Some(limit) = policy.tokens_per_minute.filter(|limit| *limit > 0) {
+            let mut used = self.tokens.iter().map(|(_, tokens)| tokens).sum::<u64>();
+
+            for (at, tokens) in &self.tokens {
+                if used < limit {
+                    break;
+                }
+
+                used -= tokens;
+                wait = wait.max(until_expired(*at));
+            }
+        }
+
+        wait
+    }
+}
+
+impl ResolverGuard {
+    /// Waits until the rate limits allow another request and counts it.
+    pub async fn acquire(&self, policy: &ResolverPolicy) {
+        loop {
+            let wait = {
+                let now = Instant::now();
+                let mut window = self.window.lock().unwrap();
+
+                window.prune(now);
+
+                let wait = window.wait(policy, now);
+
+                if wait.is_zero() {
+                    window.requests.push_back(now);
+                }
+
+                wait
+            };
+
+            if wait.is_zero() {
+                return;
+            }
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Counts the tokens a request spent against the tokens-per-minute limit.
+    pub fn spend(&self, tokens: u64) {
+        if tokens > 0 {
+            self.window
+                .lock()
+                .unwrap()
+                .tokens
+                .push_back((Instant::now(), tokens));
+        }
+    }
+
+    /// Feeds the throughput averages with a finished call.
+    pub fn observe(&self, latency_ms: u64, success: bool) {
+        let latency_ms = latency_ms.max(1) as f64;
+        let success = if success { 1.0 } else { 0.0 };
+        let mut throughput = self.throughput.lock().unwrap();
+
+        *throughput = Some(match *throughput {
+            Some(previous) => Throughput {
+                latency_ms: previous.latency_ms
+                    + THROUGHPUT_SMOOTHING * (latency_ms - previous.latency_ms),
+                success_rate: previous.success_rate
+                    + THROUGHPUT_SMOOTHING * (success - previous.success_rate),
+            },
+            None => Throughput {
+                latency_ms,
+                success_rate: success,
+            },
+        });
+    }
+
+    /// Valid units per second of a single request slot, once a call has been
+    /// observed.
+    pub fn throughput(&self) -> Option<f64> {
+        self.throughput
+            .lock()
+            .unwrap()
+            .map(|throughput| throughput.success_rate * 1000.0 / throughput.latency_ms)
+    }
+
+    /// Whether the circuit breaker lets units be assigned to the resolver.
+    pub fn is_available(&self) -> bool {
+        !self.breaker.lock().unwrap().open.is_open(Instant::now())
+    }
+
+    /// Records whether a unit computed on the resolver failed with a
+    /// transport error or timeout. Returns `true` when this opens the
+    /// breaker.
+    pub fn record(&self, policy: &ResolverPolicy, failed: bool) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+
+        if !failed {
+            breaker.consecutive_failures = 0;
+            breaker.open = BreakerOpen::Closed;
+
+            return false;
+        }
+
+        breaker.consecutive_failures = breaker.consecutive_failures.saturating_add(1);
+
+        let Some(circuit_breaker) = policy.circuit_breaker else {
+            return false;
+        };
+
+        let now = Instant::now();
+
+        if breaker.open.is_open(now)
+            || breaker.consecutive_failures < circuit_breaker.failures.max(1)
+        {
+            return false;
+        }
+
+        breaker.open = now
+            .checked_add(Duration::from_secs(circuit_breaker.cooldown_secs))
+            .map_or(BreakerOpen::ForGood, BreakerOpen::Until);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(requests_per_minute: Option<u32>, tokens_per_minute: Option<u64>) -> ResolverPolicy {
+        ResolverPolicy {
+            requests_per_minute,
+            tokens_per_minute,
+            ..ResolverPolicy::default()
+        }
+    }
+
+    fn breaker(failures: u32) -> ResolverPolicy {
+        ResolverPolicy {
+            circuit_breaker: Some(CircuitBreakerPolicy {
+                failures,
+                cooldown_secs: 60,
+            }),
+            ..ResolverPolicy::default()
+        }
+    }
+
+    #[test]
+    fn wait_is_zero_within_the_limits() {
+        let now = Instant::now();
+        let window = RateWindow {
+            requests: VecDeque::from([now]),
+            tokens: VecDeque::from([(now, 100)]),
+        };
+
+        assert_eq!(window.wait(&policy(None, None), now), Duration::ZERO);
+        assert_eq!(
+            window.wait(&policy(Some(2), Some(200)), now),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn wait_lasts_until_the_oldest_request_expires() {
+        let now = Instant::now();
+        let window = RateWindow {
+            requests: VecDeque::from([
+                now - Duration::from_secs(50),
+                now - Duration::from_secs(10),
+            ]),
+            tokens: VecDeque::new(),
+        };
+
+        assert_eq!(
+            window.wait(&policy(Some(2), None), now),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            window.wait(&policy(Some(1), None), now),
+            Duration::from_secs(50)
+        );
+    }
+
+    #[test]
+    fn wait_lasts_until_enough_tokens_expire() {
+        let now = Instant::now();
+        let window = RateWindow {
+            requests: VecDeque::new(),
+            tokens: VecDeque::from([
+                (now - Duration::from_secs(40), 60),
+                (now - Duration::from_secs(20), 60),
+            ]),
+        };
+
+        assert_eq!(
+            window.wait(&policy(None, Some(100)), now),
+            Duration::from_secs(20)
+        );
+        assert_eq!(
+            window.wait(&policy(None, Some(50)), now),
+            Duration::from_secs(40)
+        );
+    }
+
+    #[test]
+    fn record_opens_the_breaker_after_consecutive_failures() {
+        let guard = ResolverGuard::default();
+        let policy = breaker(2);
+
+        assert!(!guard.record(&policy, true));
+        assert!(guard.is_available());
+        assert!(guard.record(&policy, true));
+        assert!(!guard.is_available());
+        assert!(!guard.record(&policy, true));
+    }
+
+    #[test]
+    fn record_resets_on_success() {
+        let guard = ResolverGuard::default();
+        let policy = breaker(2);
+
+        assert!(!guard.record(&policy, true));
+        assert!(!guard.record(&policy, false));
+        assert!(!guard.record(&policy, true));
+        assert!(guard.is_available());
+    }
+
+    #[test]
+    fn record_without_a_breaker_never_opens() {
+        let guard = ResolverGuard::default();
+
+        for _ in 0..5 {
+            assert!(!guard.record(&ResolverPolicy::default(), true));
+        }
+
+        assert!(guard.is_available());
+    }
+
+    #[test]
+    fn record_survives_cooldowns_an_instant_cannot_hold() {
+        let guard = ResolverGuard::default();
+        let mut policy = breaker(1);
+        policy.circuit_breaker.as_mut().unwrap().cooldown_secs = u64::MAX;
+
+        assert!(guard.record(&policy, true));
+        assert!(!guard.is_available());
+        assert!(!guard.record(&policy, true));
+        assert!(policy.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_timeouts_a_duration_cannot_hold() {
+        let timeout = |timeout_secs| ResolverPolicy {
+            timeout_secs: Some(timeout_secs),
+            ..ResolverPolicy::default()
+        };
+
+        assert!(timeout(2.5).validate().is_ok());
+        assert!(timeout(f64::INFINITY).validate().is_err());
+        assert!(timeout(f64::NAN).validate().is_err());
+        assert!(timeout(1e30).validate().is_err());
+        assert!(timeout(-1.0).validate().is_err());
+        assert_eq!(timeout(1e30).timeout(), None);
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, ImageFormat, ImageResult, Rgba, RgbaImage,
+};
+
+pub use client::state_rgb;
+
+use super::space::CognitiveSpaceWithMemory;
+
+pub const DEFAULT_CELL_SIZE: u32 = 16;
+pub const MAX_CELL_SIZE: u32 = 64;
+pub const DEFAULT_FPS: u32 = 6;
+/// Pixels a single frame may have, 64 MiB as RGBA.
+pub const MAX_FRAME_PIXELS: u64 = 4096 * 4096;
+/// Pixels all the frames of an animation may add up to.
+pub const MAX_ANIMATION_PIXELS: u64 = 1 << 28;
+
+/// Lower values give better GIF palettes but encode slower (1..=30).
+const GIF_SPEED: i32 = 10;
+
+/// Width and height in pixels of the frame [`render_frame`] draws, or `None`
+/// if they do not fit a `u32`.
+pub fn frame_size(space: &CognitiveSpaceWithMemory, cell_size: u32) -> Option<(u32, u32)> {
+    let (width, height) = space
+        .positions()
+        .into_iter()
+        .fold((0, 0), |(width, height), (x, y)| {
+            (width.max(x + 1), height.max(y + 1))
+        });
+
+    Some((
+        u32::try_from(width).ok()?.checked_mul(cell_size)?,
+        u32::try_from(height).ok()?.checked_mul(cell_size)?,
+    ))
+}
+
+/// Pixels in the frame [`render_frame`] draws, to check against
+/// [`MAX_FRAME_PIXELS`] before drawing it.
+pub fn frame_pixels(space: &CognitiveSpaceWithMemory, cell_size: u32) -> Option<u64> {
+    frame_size(space, cell_size).map(|(width, height)| width as u64 * height as u64)
+}
+
+/// Draws every unit as a `cell_size` square, with `x` to the right and `y`
+/// downwards. Panics if [`frame_size`] does not fit.
+pub fn render_frame(space: &CognitiveSpaceWithMemory, cell_size: u32) -> RgbaImage {
+    let units = space.get_units();
+    let (width, height) = frame_size(space, cell_size).expect("frame too large");
+
+    let mut frame = RgbaImage::new(width, height);
+
+    for unit in &units {
+        let Some(last) = unit.memory.last() else {
+            continue;
+        };
+
+        let [r, g, b] = state_rgb(&last.state);
+        let (x, y) = (unit.position.0 as u32, unit.position.1 as u32);
+
+        for dx in 0..cell_size {
+            for dy in 0..cell_size {
+                frame.put_pixel(x * cell_size + dx, y * cell_size + dy, Rgba([r, g, b, 255]));
+            }
+        }
+    }
+
+    frame
+}
+
+pub fn encode_png(space: &CognitiveSpaceWithMemory, cell_size: u32) -> ImageResult<Vec<u8>> {
+    let mut png = Cursor::new(vec![]);
+
+    render_frame(space, cell_size).write_to(&mut png, ImageFormat::Png)?;
+
+    Ok(png.into_inner())
+}
+
+/// Encodes the spaces as a looping GIF, one frame each at `fps`.
+pub fn encode_gif<'a>(
+    spaces: impl IntoIterator<Item = &'a CognitiveSpaceWithMemory>,
+    cell_size: u32,
+    fps: u32,
+) -> ImageResult<Vec<u8>> {
+    let mut gif = vec![];
+
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut gif, GIF_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        let delay = Delay::from_numer_denom_ms(1000, fps.max(1));
+
+        for space in spaces {
+            encoder.encode_frame(Frame::from_parts(
+                render_frame(space, cell_size),
+                0,
+                0,
+                delay,
+            ))?;
+        }
+    }
+
+    Ok(gif)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::{space::build_lattice_with_memory, unit_next::CognitiveUnitPair};
+
+    #[test]
+    fn frame_size_is_checked() {
+        let space = build_lattice_with_memory(3, 2, 1, |_| CognitiveUnitPair {
+            rule: "r".to_string(),
+            state: "#000000".to_string(),
+        });
+
+        assert_eq!(frame_size(&space, 16), Some((48, 32)));
+        assert_eq!(frame_pixels(&space, 16), Some(48 * 32));
+        assert_eq!(frame_size(&space, u32::MAX), None);
+    }
+}
@@ -0,0 +1,238 @@
+//! Content-addressed cache of resolver answers, so a unit that sees the same
+//! memory and neighbors as before on the same resolver and model reuses the
+//! answer instead of paying for it again.
+//!
+//! Answers are kept in memory and, when the cache has a folder, as one JSON
+//! file per key there, so they survive restarts and are shared by every
+//! workspace of a root. The folder is bounded: past its limit the oldest
+//! files are removed.
+
+use std::{
+    fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
+
+use lru::LruCache;
+use tracing::warn;
+
+use super::unit_next::CognitiveUnitPair;
+use crate::life::storage::write_atomic;
+
+pub const RESPONSE_CACHE_FOLDER: &str = "response-cache";
+pub const DEFAULT_RESPONSE_CACHE_SIZE: usize = 4096;
+pub const DEFAULT_RESPONSE_CACHE_FILES: usize = 65536;
+
+#[derive(Debug)]
+pub struct ResponseCache {
+    entries: Mutex<LruCache<String, CognitiveUnitPair>>,
+    folder: Option<PathBuf>,
+    max_files: usize,
+    /// Files in `folder`, counted on the first write.
+    files: Mutex<Option<usize>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_RESPONSE_CACHE_SIZE, None)
+    }
+}
+
+impl ResponseCache {
+    /// Keeps up to `capacity` answers in memory and, with a `folder`, up to
+    /// [`DEFAULT_RESPONSE_CACHE_FILES`] on disk; see
+    /// [`ResponseCache::with_max_files`].
+    pub fn new(capacity: usize, folder: Option<PathBuf>) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(
+                NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+            )),
+            folder,
+            max_files: DEFAULT_RESPONSE_CACHE_FILES,
+            files: Mutex::new(None),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Answers kept on disk before the oldest are removed.
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files.max(1);
+        self
+    }
+
+    /// Key of a request: the resolver and model it goes to, the hash of its
+    /// system prompt and its payload.
+    pub fn key(
+        resolver_id: &str,
+        model_name: &str,
+        system_message: &str,
+        user_message: &str,
+    ) -> String {
+        let system_hash = md5::compute(system_message);
+
+        format!(
+            "{:x}",
+            md5::compute(format!(
+                "{resolver_id}\n{model_name}\n{system_hash:x}\n{user_message}"
+            ))
+        )
+    }
+
+    /// The answer cached under `key`, looked up in memory first and then on
+    /// disk.
+    pub fn get(&self, key: &str) -> Option<CognitiveUnitPair> {
+        let cached = self.entries.lock().unwrap().get(key).cloned();
+
+        let pair = cached.or_else(|| {
+            let pair = self.read(key)?;
+
+            self.entries
+                .lock()
+                .unwrap()
+                .put(key.to_string(), pair.clone());
+
+            Some(pair)
+        });
+
+        if pair.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        pair
+    }
+
+    pub fn put(&self, key: &str, pair: &CognitiveUnitPair) {
+        self.entries
+            .lock()
+            .unwrap()
+            .put(key.to_string(), pair.clone());
+
+        let Some(folder) = &self.folder else {
+            return;
+        };
+
+        let written = std::fs::create_dir_all(folder).and_then(|_| {
+            write_atomic(
+                &folder.join(format!("{key}.json")),
+                &serde_json::to_vec(pair)?,
+            )
+        });
+
+        if let Err(error) = written {
+            warn!(key, error = %error, "response_cache_write_failed");
+
+            return;
+        }
+
+        let mut files = self.files.lock().unwrap();
+        let count = files.get_or_insert_with(|| cached_files(folder).len());
+
+        *count += 1;
+
+        if *count > self.max_files {
+            match prune(folder, self.max_files) {
+                Ok(left) => *count = left,
+                Err(error) => warn!(error = %error, "response_cache_prune_failed"),
+            }
+        }
+    }
+
+    fn read(&self, key: &str) -> Option<CognitiveUnitPair> {
+        let json = std::fs::read(self.folder.as_ref()?.join(format!("{key}.json"))).ok()?;
+
+        serde_json::from_slice(&json).ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// The answer files in `folder` with when they were written.
+fn cached_files(folder: &Path) -> Vec<(SystemTime, PathBuf)> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return vec![];
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+
+            (modified, path)
+        })
+        .collect()
+}
+
+/// Removes the oldest answer files until a tenth of `max_files` is free, so
+/// pruning does not run on every write. Returns how many are left.
+fn prune(folder: &Path, max_files: usize) -> io::Result<usize> {
+    let mut files = cached_files(folder);
+    let keep = max_files - max_files / 10;
+
+    files.sort();
+
+    let excess = files.len().saturating_sub(keep);
+
+    for (_, path) in &files[..excess] {
+        fs::remove_file(path)?;
+    }
+
+    Ok(files.len() - excess)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_folder_is_pruned_past_its_limit() {
+        let folder =
+            std::env::temp_dir().join(format!("llmca-response-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+
+        let cache = ResponseCache::new(1, Some(folder.clone())).with_max_files(10);
+        let pair = CognitiveUnitPair {
+            rule: "r".to_string(),
+            state: "s".to_string(),
+        };
+
+        for i in 0..25 {
+            cache.put(&format!("key{i}"), &pair);
+        }
+
+        let files = cached_files(&folder).len();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert!((9..=10).contains(&files), "{files} files left");
+    }
+}
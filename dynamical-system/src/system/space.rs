@@ -1,4 +1,5 @@
 use chrono::Utc;
+use futures::{stream::FuturesUnordered, StreamExt};
 // use futures::{stream, StreamExt};
 use itertools::Itertools;
 use petgraph::{stable_graph::StableGraph, Undirected};
@@ -10,19 +11,27 @@ use reqwest::Client;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::{
+    life::storage::write_atomic,
+    system::assignment::{Assigner, AssignmentStrategy},
+    system::policy::{ResolverGuard, ResolverPolicy},
+    system::response_cache::ResponseCache,
     system::telemetry::StepTelemetry,
     system::unit::{CognitiveContext, LLMProvider},
-    system::unit_next::{CognitiveUnitComplex, CognitiveUnitPair, CognitiveUnitWithMemory},
+    system::unit_next::{
+        CognitiveUnitComplex, CognitiveUnitPair, CognitiveUnitWithMemory, UnitOutcome,
+    },
 };
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashMap, HashSet},
     env,
     fmt::Debug,
+    io,
     path::Path,
+    sync::Arc,
     time::{Duration, Instant},
     vec,
 };
-use tracing::{debug, info, instrument, warn};
+use tracing::{debug, info, instrument, warn, Instrument};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct CognitiveTask {
@@ -38,6 +47,52 @@ pub struct CognitiveSpaceWithMemory {
     // computing_tasks: Option<Vec<CognitiveTask>>,
 }
 
+/// Hook into a running [`CognitiveSpaceWithMemory::distributed_step_with`].
+/// Units are reported as soon as they resolve, in completion order.
+pub trait StepObserver: Send {
+    fn unit_completed(&mut self, _position: (usize, usize), _unit: &CognitiveUnitComplex) {}
+
+    fn chunk_completed(&mut self, _telemetry: &StepTelemetry) {}
+}
+
+impl StepObserver for () {}
+
+impl<T: StepObserver> StepObserver for Option<T> {
+    fn unit_completed(&mut self, position: (usize, usize), unit: &CognitiveUnitComplex) {
+        if let Some(observer) = self {
+            observer.unit_completed(position, unit);
+        }
+    }
+
+    fn chunk_completed(&mut self, telemetry: &StepTelemetry) {
+        if let Some(observer) = self {
+            observer.chunk_completed(telemetry);
+        }
+    }
+}
+
+impl<A: StepObserver, B: StepObserver> StepObserver for (A, B) {
+    fn unit_completed(&mut self, position: (usize, usize), unit: &CognitiveUnitComplex) {
+        self.0.unit_completed(position, unit);
+        self.1.unit_completed(position, unit);
+    }
+
+    fn chunk_completed(&mut self, telemetry: &StepTelemetry) {
+        self.0.chunk_completed(telemetry);
+        self.1.chunk_completed(telemetry);
+    }
+}
+
+/// Per-entity settings of a step.
+#[derive(Debug, Clone, Default)]
+pub struct StepOptions {
+    pub assignment: AssignmentStrategy,
+    /// Whether a unit that adopts a neighbor's rule also takes its species.
+    pub inherit_species: bool,
+    /// Where answers are reused from; `None` asks the resolvers every time.
+    pub response_cache: Option<Arc<ResponseCache>>,
+}
+
 pub trait CognitiveRule {
     fn compile_prompt(&self) -> String;
 }
@@ -83,15 +138,136 @@ impl CognitiveRule for MessageModelRule {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMResolver {
+    #[serde(default)]
+    name: Option<String>,
     api_url: String,
     api_key: String,
     model_name: String,
     #[serde(default)]
     provider: Option<LLMProvider>,
+    /// Disabled resolvers stay in the configuration but receive no requests.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    disabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pricing: Option<ResolverPricing>,
+    /// Requests it can take at once, relative to the other resolvers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    capacity: Option<u32>,
+    #[serde(default, skip_serializing_if = "ResolverPolicy::is_default")]
+    policy: ResolverPolicy,
+    /// Shared by every copy of the resolver, see [`LLMResolver::adopt_guard`].
+    #[serde(skip)]
+    guard: Arc<ResolverGuard>,
+}
+
+/// What a resolver charges per million tokens, in whatever currency the
+/// configuration uses. Costs are estimated from it; without one they are 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResolverPricing {
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
+}
+
+impl ResolverPricing {
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_million
+            + output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
 }
 
 impl LLMResolver {
-    fn provider(&self) -> LLMProvider {
+    pub fn new(
+        name: Option<String>,
+        api_url: String,
+        api_key: String,
+        model_name: String,
+        provider: Option<LLMProvider>,
+    ) -> Self {
+        Self {
+            name,
+            api_url,
+            api_key,
+            model_name,
+            provider,
+            disabled: false,
+            pricing: None,
+            capacity: None,
+            policy: ResolverPolicy::default(),
+            guard: Arc::default(),
+        }
+    }
+
+    /// The resolver's `name` if configured, its model name otherwise.
+    pub fn id(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.model_name)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    pub fn api_key(&self) -> &str {
+        &self.api_key
+    }
+
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.disabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.disabled = !enabled;
+    }
+
+    pub fn pricing(&self) -> Option<&ResolverPricing> {
+        self.pricing.as_ref()
+    }
+
+    pub fn set_pricing(&mut self, pricing: Option<ResolverPricing>) {
+        self.pricing = pricing;
+    }
+
+    /// Configured capacity, 1 by default.
+    pub fn capacity(&self) -> u32 {
+        self.capacity.unwrap_or(1).max(1)
+    }
+
+    pub fn set_capacity(&mut self, capacity: Option<u32>) {
+        self.capacity = capacity;
+    }
+
+    pub fn policy(&self) -> &ResolverPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: ResolverPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn guard(&self) -> &Arc<ResolverGuard> {
+        &self.guard
+    }
+
+    /// Takes over the rate limit and circuit breaker state of the resolver
+    /// with the same id in `previous`, so replacing the pool does not reset
+    /// them.
+    pub fn adopt_guard(&mut self, previous: &[LLMResolver]) {
+        if let Some(resolver) = previous.iter().find(|resolver| resolver.id() == self.id()) {
+            self.guard = resolver.guard.clone();
+        }
+    }
+
+    pub fn provider(&self) -> LLMProvider {
         self.provider
             .clone()
             .unwrap_or_else(|| LLMProvider::infer_from_api_url(&self.api_url))
@@ -103,12 +279,54 @@ impl CognitiveSpaceWithMemory {
         serde_json::from_str(json).unwrap()
     }
 
-    #[instrument(skip_all, fields(units = self.graph.node_count(), resolvers = resolvers.len()))]
+    pub fn try_load_from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
     pub async fn distributed_step(&mut self, resolvers: &[LLMResolver]) -> StepTelemetry {
+        self.distributed_step_with(resolvers, StepOptions::default(), HashMap::new(), &mut ())
+            .await
+    }
+
+    /// Runs a step, skipping the units already present in `completed` (keyed
+    /// by position) and applying their stored results instead, so an
+    /// interrupted step can be resumed without re-querying them. Each chunk
+    /// holds as many units as the resolvers' capacities add up to. Units go
+    /// to the resolver of their species when it is usable and are spread by
+    /// the assignment strategy otherwise.
+    #[instrument(skip_all, fields(units = self.graph.node_count(), resolvers = resolvers.len(), resumed = completed.len(), assignment = ?options.assignment))]
+    pub async fn distributed_step_with(
+        &mut self,
+        resolvers: &[LLMResolver],
+        options: StepOptions,
+        mut completed: HashMap<(usize, usize), CognitiveUnitComplex>,
+        observer: &mut dyn StepObserver,
+    ) -> StepTelemetry {
         let started_at = Instant::now();
         let mut nodes = self.graph.clone().node_indices().collect::<Vec<_>>();
         let mut telemetry = StepTelemetry::new(nodes.len(), resolvers.len());
 
+        if !completed.is_empty() {
+            nodes.retain(|&node| {
+                let unit = self.graph.node_weight_mut(node).unwrap();
+
+                match completed.remove(&unit.position) {
+                    Some(result) => {
+                        telemetry.record_unit(&result, resolvers);
+                        unit.add_memory(result);
+                        false
+                    }
+                    None => true,
+                }
+            });
+
+            info!(
+                resumed_units = telemetry.units_completed,
+                pending_units = nodes.len(),
+                "distributed_step_resumed"
+            );
+        }
+
         // Seed a Send-able StdRng from the thread-local RNG inside a tight
         // scope so the !Send `ThreadRng` is dropped before any `.await` and
         // the resulting future stays `Send` (required by `tokio::spawn` and
@@ -170,21 +388,53 @@ impl CognitiveSpaceWithMemory {
             return telemetry;
         }
 
-        let chunk_width = computation_units.len();
+        let chunk_width = resolvers
+            .iter()
+            .map(|resolver| resolver.capacity() as usize)
+            .sum::<usize>();
+        let mut assigner = Assigner::new(options.assignment, &self.positions(), resolvers.len());
+
         info!(
-            units_total = nodes.len(),
+            units_total = telemetry.units_total,
+            units_pending = nodes.len(),
             resolver_count = computation_units.len(),
             max_in_flight_requests = chunk_width,
+            assignment = ?options.assignment,
             "distributed_step_started"
         );
 
         for chunk in nodes.chunks(chunk_width) {
             let chunk_started_at = Instant::now();
             telemetry.record_chunk();
-            let mut tasks = vec![];
+            let mut tasks = FuturesUnordered::new();
+
+            // Units only go to resolvers whose circuit breaker is closed,
+            // which take over the share of the ones that tripped.
+            let active = available_resolvers(resolvers);
+            let assigned = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, &node)| {
+                    let unit = self.graph.node_weight(node).unwrap();
+
+                    unit.species
+                        .as_deref()
+                        .and_then(|species| {
+                            active
+                                .iter()
+                                .copied()
+                                .find(|&index| resolvers[index].id() == species)
+                        })
+                        .unwrap_or_else(|| assigner.assign(resolvers, &active, unit.position, i))
+                })
+                .collect::<Vec<_>>();
+
+            // The neighbors' species as the units saw them, to tell whose
+            // rule a unit adopted.
+            let mut neighbor_species = vec![];
 
             for (i, &node) in chunk.iter().enumerate() {
-                let resolver_index = i % computation_units.len();
+                let resolver_index = assigned[i];
                 let neighbors = self
                     .graph
                     .neighbors(node)
@@ -198,25 +448,54 @@ impl CognitiveSpaceWithMemory {
                     })
                     .collect::<Vec<_>>();
 
+                neighbor_species.push(
+                    self.graph
+                        .neighbors(node)
+                        .map(|neighbor| {
+                            let neighbor_unit = self.graph.node_weight(neighbor).unwrap();
+
+                            (
+                                neighbor_unit.memory.last().unwrap().rule.clone(),
+                                neighbor_unit.species.clone(),
+                            )
+                        })
+                        .collect::<Vec<_>>(),
+                );
+
                 let unit = self.graph.node_weight_mut(node).unwrap().clone();
-                let ctx = CognitiveContext {
-                    client: Box::new(Client::new()),
-                    base_api: computation_units[resolver_index].0.to_string(),
-                    model_name: computation_units[resolver_index].1.to_string(),
-                    secret_key: computation_units[resolver_index].2.to_string(),
-                    provider: computation_units[resolver_index].3.clone(),
-                };
+                let route = failover_route(resolvers, resolver_index)
+                    .into_iter()
+                    .map(|index| CognitiveContext {
+                        client: Box::new(Client::new()),
+                        resolver_id: resolvers[index].id().to_string(),
+                        base_api: computation_units[index].0.to_string(),
+                        model_name: computation_units[index].1.to_string(),
+                        secret_key: computation_units[index].2.to_string(),
+                        provider: computation_units[index].3.clone(),
+                        policy: resolvers[index].policy().clone(),
+                        guard: resolvers[index].guard().clone(),
+                        cache: options.response_cache.clone(),
+                    })
+                    .collect::<Vec<_>>();
 
-                tasks.push(tokio::spawn(async move {
-                    // unit.calculate_next_state(&ctx, neighbors).await
+                // Carry the step's span into the task so each unit shows up as
+                // its child in exported traces.
+                let task = tokio::spawn(
+                    async move {
+                        // unit.calculate_next_state(&ctx, neighbors).await
 
-                    unit.calculate_next_complex(&ctx, neighbors).await
-                }));
-            }
+                        unit.calculate_next_routed(&route, neighbors).await
+                    }
+                    .in_current_span(),
+                );
 
-            let next_states = futures::future::join_all(tasks).await;
+                tasks.push(async move { (i, task.await) });
+            }
 
-            for (i, next_state) in next_states.into_iter().enumerate() {
+            // Applied as each unit resolves so observers see progress within
+            // the chunk. Every unit of the chunk read its neighbors above, so
+            // the order does not change the result.
+            while let Some((i, next_state)) = tasks.next().await {
                 let node = chunk[i];
                 let unit = self.graph.node_weight_mut(node).unwrap();
 
@@ -233,6 +512,15 @@ impl CognitiveSpaceWithMemory {
                             state: previous.state,
                             neighbors: vec![],
                             feedback: format!("LLM request failed: task join error: {err}"),
+                            tokens: 0,
+                            input_tokens: 0,
+                            output_tokens: 0,
+                            resolver_id: Some(resolvers[assigned[i]].id().to_string()),
+                            latency_ms: 0,
+                            outcome: UnitOutcome::TransportError,
+                            attempts: vec![],
+                            hedged_to: None,
+                            cached: false,
                         }
                     }
                 };
@@ -240,10 +528,34 @@ impl CognitiveSpaceWithMemory {
                 // unit.state = next_state.calculated_state;
                 // unit.feedback = next_state.feedback;
 
-                telemetry.record_unit(&next_state);
+                if options.inherit_species {
+                    if let Some(species) = adopted_species(unit, &next_state, &neighbor_species[i])
+                    {
+                        debug!(
+                            position = ?unit.position,
+                            from = unit.species.as_deref().unwrap_or_default(),
+                            to = %species,
+                            rule = %next_state.rule,
+                            "unit_species_adopted"
+                        );
+
+                        unit.species = Some(species);
+                        telemetry.species_adoptions += 1;
+                    }
+                }
+
+                telemetry.record_unit(&next_state, resolvers);
+
+                if options.response_cache.is_some() {
+                    telemetry.record_cache_lookup(next_state.cached);
+                }
+
+                observer.unit_completed(unit.position, &next_state);
                 unit.add_memory(next_state);
             }
 
+            observer.chunk_completed(&telemetry);
+
             log_slow_chunk(
                 chunk.len(),
                 chunk_started_at.elapsed(),
@@ -253,6 +565,7 @@ impl CognitiveSpaceWithMemory {
             );
         }
 
+        telemetry.species = self.species_counts();
         telemetry.finish(started_at.elapsed(), self.unique_state_count());
         info!(
             units_total = telemetry.units_total,
@@ -262,19 +575,29 @@ impl CognitiveSpaceWithMemory {
             llm_failures = telemetry.llm_failures,
             parse_failures = telemetry.parse_failures,
             unique_states = telemetry.unique_states,
+            input_tokens = telemetry.input_tokens,
+            output_tokens = telemetry.output_tokens,
+            cost = telemetry.cost,
             elapsed_ms = telemetry.elapsed_ms,
             "distributed_step_completed"
         );
 
-        telemetry
-    }
+        for (resolver_id, stats) in &telemetry.resolver_stats {
+            info!(
+                resolver_id,
+                calls = stats.calls,
+                success_rate = stats.success_rate,
+                transport_failures = stats.transport_failures,
+                structured_output_failures = stats.structured_output_failures,
+                timeouts = stats.timeouts,
+                latency_p50_ms = stats.latency_p50_ms,
+                latency_p95_ms = stats.latency_p95_ms,
+                latency_p99_ms = stats.latency_p99_ms,
+                "distributed_step_resolver_stats"
+            );
+        }
 
-    pub async fn distributed_step_with_tasks(
-        &mut self,
-        resolvers: &[LLMResolver],
-        _handle: &tokio::runtime::Handle,
-    ) -> StepTelemetry {
-        self.distributed_step(resolvers).await
+        telemetry
     }
 
     pub fn generate_graph(&self) -> StableGraph<CognitiveUnitWithMemory, (), Undirected> {
@@ -286,21 +609,51 @@ impl CognitiveSpaceWithMemory {
     }
 
     pub fn set_unit(&mut self, i: usize, j: usize, unit: CognitiveUnitWithMemory) {
-        let xy_to_index = |i: usize, j: usize| -> usize { i * j + j };
-
-        let node = self.graph.node_indices().nth(xy_to_index(i, j)).unwrap();
-
-        let internal_unit = self.graph.node_weight_mut(node).unwrap();
+        let internal_unit = self.unit_mut((i, j)).unwrap();
 
         internal_unit.memory = unit.memory;
         internal_unit.memory_size = unit.memory_size;
         internal_unit.position = unit.position;
     }
 
+    pub fn unit_mut(&mut self, position: (usize, usize)) -> Option<&mut CognitiveUnitWithMemory> {
+        self.graph
+            .node_weights_mut()
+            .find(|unit| unit.position == position)
+    }
+
+    /// Every unit keyed by its position, for edits touching many units
+    /// without a scan per unit.
+    pub fn units_by_position_mut(
+        &mut self,
+    ) -> HashMap<(usize, usize), &mut CognitiveUnitWithMemory> {
+        self.graph
+            .node_weights_mut()
+            .map(|unit| (unit.position, unit))
+            .collect()
+    }
+
+    pub fn positions(&self) -> Vec<(usize, usize)> {
+        self.graph
+            .node_weights()
+            .map(|unit| unit.position)
+            .collect()
+    }
+
     pub fn serialize_in_pretty_json(&self) -> String {
         serde_json::to_string_pretty(&self).unwrap()
     }
 
+    /// Units per species, leaving out units without one.
+    pub fn species_counts(&self) -> BTreeMap<String, usize> {
+        self.graph
+            .node_weights()
+            .filter_map(|unit| unit.species.clone())
+            .counts()
+            .into_iter()
+            .collect()
+    }
+
     fn unique_state_count(&self) -> usize {
         self.graph
             .node_weights()
@@ -318,15 +671,87 @@ impl CognitiveSpaceWithMemory {
     // }
 }
 
+/// Which cells count as neighbors on a 2D lattice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Neighborhood {
+    /// The 8 surrounding cells, diagonals included.
+    #[default]
+    Moore,
+    /// The 4 orthogonal cells.
+    VonNeumann,
+}
+
+impl Neighborhood {
+    fn offsets(&self) -> &'static [(isize, isize)] {
+        match self {
+            Self::Moore => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+            Self::VonNeumann => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatticeTopology {
+    #[serde(default)]
+    pub neighborhood: Neighborhood,
+    /// Connects opposite edges, turning the grid into a torus.
+    #[serde(default = "default_wrap")]
+    pub wrap: bool,
+}
+
+fn default_wrap() -> bool {
+    true
+}
+
+impl Default for LatticeTopology {
+    fn default() -> Self {
+        Self {
+            neighborhood: Neighborhood::default(),
+            wrap: default_wrap(),
+        }
+    }
+}
+
+/// Index of `position` in lattice order: x-major, so `(x, y)` of a lattice
+/// of `size` sits at `x * size.1 + y`. Nodes, initial states and species are
+/// all laid out in this order.
+pub fn lattice_index(position: (usize, usize), size: (usize, usize)) -> usize {
+    position.0 * size.1 + position.1
+}
+
 pub fn build_lattice_with_memory(
     n: usize,
     m: usize,
     memory_size: usize,
     cognitive_unit_init_state: impl Fn((usize, usize)) -> CognitiveUnitPair,
 ) -> CognitiveSpaceWithMemory {
-    let xy_to_index = |i: usize, j: usize| -> usize { i * m + j };
+    build_lattice_with_topology(
+        n,
+        m,
+        memory_size,
+        LatticeTopology::default(),
+        cognitive_unit_init_state,
+    )
+}
 
-    // let mut rng = thread_rng();
+pub fn build_lattice_with_topology(
+    n: usize,
+    m: usize,
+    memory_size: usize,
+    topology: LatticeTopology,
+    cognitive_unit_init_state: impl Fn((usize, usize)) -> CognitiveUnitPair,
+) -> CognitiveSpaceWithMemory {
+    let xy_to_index = |i: usize, j: usize| -> usize { lattice_index((i, j), (n, m)) };
 
     let mut graph =
         StableGraph::<CognitiveUnitWithMemory, (), Undirected>::with_capacity(n * m, 8 * n * m);
@@ -334,16 +759,6 @@ pub fn build_lattice_with_memory(
     let (nodes, positions): (Vec<_>, Vec<_>) = (0..n)
         .cartesian_product(0..m)
         .map(|position| {
-            // let state = self.initial_states.choose(&mut rng).unwrap().to_owned();
-            // let rule = self.rule.compile_prompt();
-
-            // let unit = CognitiveUnitWithMemory {
-            //     rule,
-            //     state,
-            //     position,
-            //     feedback: None,
-            // };
-
             let first_unit = cognitive_unit_init_state(position);
 
             let unit = CognitiveUnitWithMemory::new(
@@ -354,6 +769,15 @@ pub fn build_lattice_with_memory(
                     state: first_unit.state.clone(),
                     neighbors: vec![],
                     feedback: "".to_string(),
+                    tokens: 0,
+                    input_tokens: 0,
+                    output_tokens: 0,
+                    resolver_id: None,
+                    latency_ms: 0,
+                    outcome: UnitOutcome::Ok,
+                    attempts: vec![],
+                    hedged_to: None,
+                    cached: false,
                 }],
                 memory_size,
             );
@@ -362,52 +786,29 @@ pub fn build_lattice_with_memory(
         })
         .unzip();
 
-    positions.iter().for_each(|&(i, j)| {
-        let i_s1 = if i.overflowing_sub(1).1 { n - 1 } else { i };
-        let j_s1 = if j.overflowing_sub(1).1 { m - 1 } else { j };
-
-        let i_a1 = (i + 1) % n;
-        let j_a1 = (j + 1) % m;
-
-        let n_edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(i_a1, j)]);
-        let ne_edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(i_a1, j_a1)]);
-        let e_edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(i, j_a1)]);
-        let se_edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(i_s1, j_a1)]);
-        let s_edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(i_s1, j)]);
-        let sw_edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(i_s1, j_s1)]);
-        let w_edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(i, j_s1)]);
-        let nw_edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(i_a1, j_s1)]);
-
-        if !graph.contains_edge(n_edge.0, n_edge.1) && n_edge.0 != n_edge.1 {
-            graph.add_edge(n_edge.0, n_edge.1, ());
-        }
-
-        if !graph.contains_edge(ne_edge.0, ne_edge.1) && ne_edge.0 != ne_edge.1 {
-            graph.add_edge(ne_edge.0, ne_edge.1, ());
-        }
-
-        if !graph.contains_edge(e_edge.0, e_edge.1) && e_edge.0 != e_edge.1 {
-            graph.add_edge(e_edge.0, e_edge.1, ());
-        }
-
-        if !graph.contains_edge(se_edge.0, se_edge.1) && se_edge.0 != se_edge.1 {
-            graph.add_edge(se_edge.0, se_edge.1, ());
-        }
+    let shift = |value: usize, delta: isize, len: usize| -> Option<usize> {
+        let shifted = value as isize + delta;
 
-        if !graph.contains_edge(s_edge.0, s_edge.1) && s_edge.0 != s_edge.1 {
-            graph.add_edge(s_edge.0, s_edge.1, ());
+        if (0..len as isize).contains(&shifted) {
+            Some(shifted as usize)
+        } else if topology.wrap {
+            Some(shifted.rem_euclid(len as isize) as usize)
+        } else {
+            None
         }
+    };
 
-        if !graph.contains_edge(sw_edge.0, sw_edge.1) && sw_edge.0 != sw_edge.1 {
-            graph.add_edge(sw_edge.0, sw_edge.1, ());
-        }
+    positions.iter().for_each(|&(i, j)| {
+        for &(di, dj) in topology.neighborhood.offsets() {
+            let (Some(ni), Some(nj)) = (shift(i, di, n), shift(j, dj, m)) else {
+                continue;
+            };
 
-        if !graph.contains_edge(w_edge.0, w_edge.1) && w_edge.0 != w_edge.1 {
-            graph.add_edge(w_edge.0, w_edge.1, ());
-        }
+            let edge = (nodes[xy_to_index(i, j)], nodes[xy_to_index(ni, nj)]);
 
-        if !graph.contains_edge(nw_edge.0, nw_edge.1) && nw_edge.0 != nw_edge.1 {
-            graph.add_edge(nw_edge.0, nw_edge.1, ());
+            if !graph.contains_edge(edge.0, edge.1) && edge.0 != edge.1 {
+                graph.add_edge(edge.0, edge.1, ());
+            }
         }
     });
 
@@ -484,26 +885,123 @@ pub fn load_llm_resolvers_from_env() -> Vec<LLMResolver> {
         .enumerate()
         .zip(models.iter())
         .zip(secret_keys.iter())
-        .map(
-            |(((index, base_api), model_name), secret_key)| LLMResolver {
-                api_url: base_api.to_string(),
-                model_name: model_name.to_string(),
-                api_key: secret_key.to_string(),
-                provider: providers
+        .map(|(((index, base_api), model_name), secret_key)| {
+            LLMResolver::new(
+                None,
+                base_api.to_string(),
+                secret_key.to_string(),
+                model_name.to_string(),
+                providers
                     .as_ref()
                     .and_then(|providers| LLMProvider::parse(&providers[index]))
                     .or_else(|| Some(LLMProvider::infer_from_api_url(base_api))),
-            },
-        )
+            )
+        })
         .collect()
 }
 
+/// Indices of the resolvers whose circuit breaker is closed, or of all of
+/// them if every breaker is open.
+fn available_resolvers(resolvers: &[LLMResolver]) -> Vec<usize> {
+    let available = (0..resolvers.len())
+        .filter(|&i| resolvers[i].guard().is_available())
+        .collect::<Vec<_>>();
+
+    if available.is_empty() {
+        warn!(
+            resolver_count = resolvers.len(),
+            "distributed_step_all_circuits_open"
+        );
+
+        return (0..resolvers.len()).collect();
+    }
+
+    available
+}
+
+/// The species a unit takes on by switching to the rule of its neighbors:
+/// the most common among the neighbors that hold that rule, ties going to
+/// the first id, if it is not the unit's own.
+fn adopted_species(
+    unit: &CognitiveUnitWithMemory,
+    next: &CognitiveUnitComplex,
+    neighbors: &[(String, Option<String>)],
+) -> Option<String> {
+    let previous = unit.memory.last()?;
+
+    if !next.outcome.is_success() || next.rule == previous.rule {
+        return None;
+    }
+
+    neighbors
+        .iter()
+        .filter(|(rule, _)| *rule == next.rule)
+        .filter_map(|(_, species)| species.clone())
+        .counts()
+        .into_iter()
+        .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then_with(|| b.cmp(a)))
+        .map(|(species, _)| species)
+        .filter(|species| unit.species.as_ref() != Some(species))
+}
+
+/// `primary` followed by the resolvers of its failover chain that are in
+/// `resolvers` with a closed circuit breaker, without repeats.
+fn failover_route(resolvers: &[LLMResolver], primary: usize) -> Vec<usize> {
+    let mut route = vec![primary];
+
+    for resolver_id in &resolvers[primary].policy().failover {
+        let index = resolvers
+            .iter()
+            .position(|resolver| resolver.id() == resolver_id.as_str());
+
+        if let Some(index) =
+            index.filter(|index| !route.contains(index) && resolvers[*index].guard().is_available())
+        {
+            route.push(index);
+        }
+    }
+
+    route
+}
+
 pub fn load_llm_resolvers_from_toml<P: AsRef<Path>>(path: P) -> Vec<LLMResolver> {
-    let toml = std::fs::read_to_string(path).unwrap();
+    try_load_llm_resolvers_from_toml(path).unwrap()
+}
+
+pub fn try_load_llm_resolvers_from_toml<P: AsRef<Path>>(path: P) -> io::Result<Vec<LLMResolver>> {
+    let toml = std::fs::read_to_string(path)?;
+
+    let resolvers: TomlConfig =
+        toml::from_str(&toml).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    validate_policies(&resolvers.resolvers, io::ErrorKind::InvalidData)?;
+
+    Ok(resolvers.resolvers)
+}
 
-    let resolvers: TomlConfig = toml::from_str(&toml).unwrap();
+/// Rewrites the resolvers file. Comments in the previous file are lost.
+pub fn save_llm_resolvers_to_toml<P: AsRef<Path>>(
+    path: P,
+    resolvers: &[LLMResolver],
+) -> io::Result<()> {
+    validate_policies(resolvers, io::ErrorKind::InvalidInput)?;
+
+    let toml = toml::to_string_pretty(&TomlConfig {
+        resolvers: resolvers.to_vec(),
+    })
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    write_atomic(path.as_ref(), toml.as_bytes())
+}
+
+fn validate_policies(resolvers: &[LLMResolver], kind: io::ErrorKind) -> io::Result<()> {
+    for resolver in resolvers {
+        resolver.policy().validate().map_err(|message| {
+            io::Error::new(kind, format!("resolver {}: {message}", resolver.id()))
+        })?;
+    }
 
-    resolvers.resolvers
+    Ok(())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,8 +1,11 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
 use serde_derive::{Deserialize, Serialize};
 
-use super::unit_next::CognitiveUnitComplex;
+use super::{
+    space::LLMResolver,
+    unit_next::{CognitiveUnitComplex, UnitOutcome},
+};
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct StepTelemetry {
@@ -10,12 +13,142 @@ pub struct StepTelemetry {
     pub units_completed: usize,
     pub resolver_count: usize,
     pub chunks: usize,
+    /// Transport errors and timeouts.
     pub llm_failures: usize,
+    /// Schema violations and rejected answers.
     pub parse_failures: usize,
+    /// Completed units by how their computation ended.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub outcomes: BTreeMap<UnitOutcome, usize>,
+    /// Units computed after failing on at least one other resolver.
+    #[serde(default)]
+    pub failovers: usize,
+    /// Units a hedged request was sent for, and how many of them the hedge
+    /// answered first.
+    #[serde(default)]
+    pub hedged: usize,
+    #[serde(default)]
+    pub hedge_wins: usize,
+    /// Units per species once the step finished.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub species: BTreeMap<String, usize>,
+    /// Units that took on another species by adopting a neighbor's rule.
+    #[serde(default)]
+    pub species_adoptions: usize,
+    /// Units answered from the response cache and units whose lookup missed,
+    /// while the cache was enabled.
+    #[serde(default)]
+    pub cache_hits: usize,
+    #[serde(default)]
+    pub cache_misses: usize,
+    #[serde(default)]
+    pub cache_hit_rate: f64,
     pub unique_states: usize,
+    #[serde(default)]
+    pub total_tokens: u64,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Estimated from the resolvers' price tables.
+    #[serde(default)]
+    pub cost: f64,
+    /// Tokens and cost of the step, by resolver id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub resolvers: BTreeMap<String, ResolverUsage>,
+    /// Call counts, failure classes and latency percentiles, by resolver id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub resolver_stats: BTreeMap<String, ResolverStats>,
     pub elapsed_ms: u64,
 }
 
+/// What one resolver computed and spent.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ResolverUsage {
+    pub units: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+}
+
+impl ResolverUsage {
+    pub fn add(&mut self, other: &ResolverUsage) {
+        self.units += other.units;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.total_tokens += other.total_tokens;
+        self.cost += other.cost;
+    }
+}
+
+/// How one resolver behaved during a step. Latencies cover failed calls too.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ResolverStats {
+    pub calls: usize,
+    /// Calls that yielded a valid unit, retried ones included.
+    pub successes: usize,
+    #[serde(default)]
+    pub retried_ok: usize,
+    pub transport_failures: usize,
+    pub timeouts: usize,
+    pub structured_output_failures: usize,
+    #[serde(default)]
+    pub validation_rejections: usize,
+    pub success_rate: f64,
+    pub latency_p50_ms: u64,
+    pub latency_p95_ms: u64,
+    pub latency_p99_ms: u64,
+    pub latency_max_ms: u64,
+    #[serde(skip)]
+    latencies_ms: Vec<u64>,
+}
+
+impl ResolverStats {
+    fn record(&mut self, outcome: UnitOutcome, latency_ms: u64) {
+        self.calls += 1;
+        self.latencies_ms.push(latency_ms);
+
+        match outcome {
+            UnitOutcome::Ok => self.successes += 1,
+            UnitOutcome::RetriedOk => {
+                self.successes += 1;
+                self.retried_ok += 1;
+            }
+            UnitOutcome::TransportError => self.transport_failures += 1,
+            UnitOutcome::Timeout => self.timeouts += 1,
+            UnitOutcome::SchemaViolation => self.structured_output_failures += 1,
+            UnitOutcome::ValidationRejected => self.validation_rejections += 1,
+        }
+    }
+
+    /// Latency of every call of the step, ascending once the step finished.
+    /// Not persisted.
+    pub fn latencies_ms(&self) -> &[u64] {
+        &self.latencies_ms
+    }
+
+    fn finish(&mut self) {
+        self.latencies_ms.sort_unstable();
+        self.success_rate = self.successes as f64 / self.calls.max(1) as f64;
+        self.latency_p50_ms = percentile(&self.latencies_ms, 50);
+        self.latency_p95_ms = percentile(&self.latencies_ms, 95);
+        self.latency_p99_ms = percentile(&self.latencies_ms, 99);
+        self.latency_max_ms = self.latencies_ms.last().copied().unwrap_or_default();
+    }
+}
+
+/// Nearest-rank percentile of ascending `sorted` values.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+
+    sorted[rank - 1]
+}
+
 impl StepTelemetry {
     pub fn new(units_total: usize, resolver_count: usize) -> Self {
         Self {
@@ -29,19 +162,117 @@ impl StepTelemetry {
         self.chunks += 1;
     }
 
-    pub fn record_unit(&mut self, unit: &CognitiveUnitComplex) {
+    /// Counts a computed unit, charging its tokens to the resolver that
+    /// computed it and those of its failed attempts to the resolvers that
+    /// made them, priced from their entries in `resolvers`.
+    pub fn record_unit(&mut self, unit: &CognitiveUnitComplex, resolvers: &[LLMResolver]) {
         self.units_completed += 1;
+        self.total_tokens += unit.tokens;
+        self.input_tokens += unit.input_tokens;
+        self.output_tokens += unit.output_tokens;
 
-        if unit.feedback.starts_with("LLM request failed") {
+        if let Some(resolver_id) = &unit.resolver_id {
+            self.charge(
+                resolver_id,
+                ResolverUsage {
+                    units: 1,
+                    input_tokens: unit.input_tokens,
+                    output_tokens: unit.output_tokens,
+                    total_tokens: unit.tokens,
+                    cost: 0.0,
+                },
+                resolvers,
+            );
+
+            // A cached answer made no call.
+            if !unit.cached {
+                self.resolver_stats
+                    .entry(resolver_id.clone())
+                    .or_default()
+                    .record(unit.outcome, unit.latency_ms);
+            }
+        }
+
+        for attempt in &unit.attempts {
+            let total_tokens = attempt.input_tokens + attempt.output_tokens;
+
+            self.total_tokens += total_tokens;
+            self.input_tokens += attempt.input_tokens;
+            self.output_tokens += attempt.output_tokens;
+
+            self.charge(
+                &attempt.resolver_id,
+                ResolverUsage {
+                    units: 0,
+                    input_tokens: attempt.input_tokens,
+                    output_tokens: attempt.output_tokens,
+                    total_tokens,
+                    cost: 0.0,
+                },
+                resolvers,
+            );
+
+            self.resolver_stats
+                .entry(attempt.resolver_id.clone())
+                .or_default()
+                .record(attempt.outcome, attempt.latency_ms);
+        }
+
+        if !unit.attempts.is_empty() {
+            self.failovers += 1;
+        }
+
+        if let Some(hedged_to) = &unit.hedged_to {
+            self.hedged += 1;
+
+            if unit.resolver_id.as_ref() == Some(hedged_to) {
+                self.hedge_wins += 1;
+            }
+        }
+
+        *self.outcomes.entry(unit.outcome).or_default() += 1;
+
+        if unit.outcome.is_llm_failure() {
             self.llm_failures += 1;
-        } else if unit.feedback.starts_with("Structured output failed") || !unit.feedback.is_empty()
-        {
+        } else if unit.outcome.is_parse_failure() {
             self.parse_failures += 1;
         }
     }
 
+    pub fn record_cache_lookup(&mut self, hit: bool) {
+        if hit {
+            self.cache_hits += 1;
+        } else {
+            self.cache_misses += 1;
+        }
+    }
+
+    /// Adds `usage` to `resolver_id`, pricing its tokens.
+    fn charge(&mut self, resolver_id: &str, mut usage: ResolverUsage, resolvers: &[LLMResolver]) {
+        usage.cost = resolvers
+            .iter()
+            .find(|resolver| resolver.id() == resolver_id)
+            .and_then(LLMResolver::pricing)
+            .map_or(0.0, |pricing| {
+                pricing.cost(usage.input_tokens, usage.output_tokens)
+            });
+
+        self.cost += usage.cost;
+
+        self.resolvers
+            .entry(resolver_id.to_string())
+            .or_default()
+            .add(&usage);
+    }
+
     pub fn finish(&mut self, elapsed: Duration, unique_states: usize) {
         self.elapsed_ms = elapsed.as_millis().try_into().unwrap_or(u64::MAX);
         self.unique_states = unique_states;
+        self.cache_hit_rate =
+            self.cache_hits as f64 / (self.cache_hits + self.cache_misses).max(1) as f64;
+
+        for stats in self.resolver_stats.values_mut() {
+            stats.finish();
+        }
     }
 }
@@ -4,7 +4,13 @@ use schemars::{schema_for, JsonSchema};
 use serde_derive::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::system::api::ChatCompletionResponse;
+use std::sync::Arc;
+
+use crate::system::{
+    api::ChatCompletionResponse,
+    policy::{ResolverGuard, ResolverPolicy},
+    response_cache::ResponseCache,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -41,10 +47,16 @@ pub struct CognitiveUnit {
 
 pub struct CognitiveContext {
     pub client: Box<Client>,
+    pub resolver_id: String,
     pub base_api: String,
     pub model_name: String,
     pub secret_key: String,
     pub provider: LLMProvider,
+    pub policy: ResolverPolicy,
+    pub guard: Arc<ResolverGuard>,
+    /// Answers are looked up here before the resolver is asked, and valid
+    /// ones stored; `None` asks the resolver every time.
+    pub cache: Option<Arc<ResponseCache>>,
 }
 
 pub struct LLMComputationResult {
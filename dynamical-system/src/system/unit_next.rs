@@ -2,28 +2,214 @@ use chrono::{DateTime, Utc};
 
 use rig::{
     client::{CompletionClient, Nothing},
-    completion::TypedPrompt,
-    extractor::ExtractorBuilder,
+    completion::{StructuredOutputError, TypedPrompt},
+    extractor::{ExtractionError, ExtractorBuilder},
     providers::{ollama, openrouter},
 };
 use schemars::{schema_for, JsonSchema};
 
+use futures::future::{select, Either};
 use serde_derive::{Deserialize, Serialize};
 use serde_json::json;
-use std::time::Instant;
-use tracing::{debug, instrument, warn};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use tracing::{debug, field, instrument, warn, Span};
 
-use crate::system::unit::{CognitiveContext, LLMProvider};
+use crate::system::{
+    response_cache::ResponseCache,
+    unit::{CognitiveContext, LLMProvider},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(from = "StoredUnitComplex")]
 pub struct CognitiveUnitComplex {
     pub timestamp: DateTime<Utc>,
     pub rule: String,
     pub state: String,          // in json format
     pub neighbors: Vec<String>, // in json format
     pub feedback: String,
+    /// Tokens spent computing this entry; 0 for fallbacks and manual edits.
+    #[serde(default)]
+    pub tokens: u64,
+    /// Prompt and completion shares of `tokens`.
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Resolver that computed this entry, if one was asked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolver_id: Option<String>,
+    /// How long the resolver took to answer, failed calls included.
+    #[serde(default)]
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub outcome: UnitOutcome,
+    /// Calls on other resolvers that failed before this entry was produced,
+    /// oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<ResolverAttempt>,
+    /// Resolver a hedged request for this entry went to, if one was sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hedged_to: Option<String>,
+    /// Reused from the response cache rather than asked of the resolver.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+}
+
+/// A [`CognitiveUnitComplex`] as snapshots hold it. Entries written before
+/// outcomes were recorded have none; theirs is recovered from the feedback
+/// the failure left, see [`UnitOutcome::from_legacy_feedback`].
+#[derive(Deserialize, JsonSchema)]
+struct StoredUnitComplex {
+    timestamp: DateTime<Utc>,
+    rule: String,
+    state: String,
+    neighbors: Vec<String>,
+    feedback: String,
+    #[serde(default)]
+    tokens: u64,
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+    #[serde(default)]
+    resolver_id: Option<String>,
+    #[serde(default)]
+    latency_ms: u64,
+    #[serde(default)]
+    outcome: Option<UnitOutcome>,
+    #[serde(default)]
+    attempts: Vec<ResolverAttempt>,
+    #[serde(default)]
+    hedged_to: Option<String>,
+    #[serde(default)]
+    cached: bool,
+}
+
+impl From<StoredUnitComplex> for CognitiveUnitComplex {
+    fn from(stored: StoredUnitComplex) -> Self {
+        let outcome = stored
+            .outcome
+            .unwrap_or_else(|| UnitOutcome::from_legacy_feedback(&stored.feedback));
+
+        Self {
+            timestamp: stored.timestamp,
+            rule: stored.rule,
+            state: stored.state,
+            neighbors: stored.neighbors,
+            feedback: stored.feedback,
+            tokens: stored.tokens,
+            input_tokens: stored.input_tokens,
+            output_tokens: stored.output_tokens,
+            resolver_id: stored.resolver_id,
+            latency_ms: stored.latency_ms,
+            outcome,
+            attempts: stored.attempts,
+            hedged_to: stored.hedged_to,
+            cached: stored.cached,
+        }
+    }
 }
 
+/// A resolver call that did not produce the entry it was made for.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ResolverAttempt {
+    pub resolver_id: String,
+    pub outcome: UnitOutcome,
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    pub feedback: String,
+}
+
+impl ResolverAttempt {
+    fn new(unit: &CognitiveUnitComplex) -> Self {
+        Self {
+            resolver_id: unit.resolver_id.clone().unwrap_or_default(),
+            outcome: unit.outcome,
+            latency_ms: unit.latency_ms,
+            input_tokens: unit.input_tokens,
+            output_tokens: unit.output_tokens,
+            feedback: unit.feedback.clone(),
+        }
+    }
+}
+
+/// How the computation of an entry ended. Entries no resolver was asked for,
+/// such as initial states and manual edits, are `Ok`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitOutcome {
+    #[default]
+    Ok,
+    /// Valid after the first answer violated the schema or was rejected.
+    RetriedOk,
+    /// The request could not be sent or the API answered with an error.
+    TransportError,
+    Timeout,
+    /// The answer was not a `CognitiveUnitPair`.
+    SchemaViolation,
+    /// The answer parsed but failed [`validate_pair`], e.g. an empty state.
+    ValidationRejected,
+}
+
+impl UnitOutcome {
+    pub fn is_success(self) -> bool {
+        matches!(self, UnitOutcome::Ok | UnitOutcome::RetriedOk)
+    }
+
+    /// The resolver could not be reached or did not answer in time.
+    pub fn is_llm_failure(self) -> bool {
+        matches!(self, UnitOutcome::TransportError | UnitOutcome::Timeout)
+    }
+
+    /// The resolver answered, but not with a usable pair.
+    pub fn is_parse_failure(self) -> bool {
+        matches!(
+            self,
+            UnitOutcome::SchemaViolation | UnitOutcome::ValidationRejected
+        )
+    }
+
+    /// The outcome of an entry saved before outcomes were recorded, from the
+    /// prefix its failure left in the feedback. Any other feedback, such as a
+    /// manual edit's message, belongs to a successful entry.
+    pub fn from_legacy_feedback(feedback: &str) -> Self {
+        if feedback.starts_with("LLM request failed") {
+            if feedback.contains("timed out") || feedback.contains("timeout") {
+                UnitOutcome::Timeout
+            } else {
+                UnitOutcome::TransportError
+            }
+        } else if feedback.starts_with("Structured output failed") {
+            UnitOutcome::SchemaViolation
+        } else {
+            UnitOutcome::Ok
+        }
+    }
+}
+
+/// Answers that violated the schema or were rejected are asked for again this
+/// many times before the unit keeps its previous rule and state.
+const STRUCTURED_OUTPUT_RETRIES: usize = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CognitiveUnitPair {
     pub rule: String,
@@ -44,6 +230,15 @@ impl Default for CognitiveUnitComplex {
             state: "".to_string(),
             neighbors: vec![],
             feedback: "".to_string(),
+            tokens: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            resolver_id: None,
+            latency_ms: 0,
+            outcome: UnitOutcome::Ok,
+            attempts: vec![],
+            hedged_to: None,
+            cached: false,
         }
     }
 }
@@ -67,6 +262,10 @@ pub struct CognitiveUnitWithMemory {
     pub memory_size: usize,
 
     pub position: (usize, usize),
+    /// Resolver that computes this unit whenever it can, when the model is
+    /// part of the experiment rather than left to the scheduler.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub species: Option<String>,
 }
 
 impl CognitiveUnitWithMemory {
@@ -86,6 +285,7 @@ impl CognitiveUnitWithMemory {
 
             memory,
             memory_size,
+            species: None,
         }
     }
 
@@ -97,7 +297,136 @@ impl CognitiveUnitWithMemory {
         }
     }
 
-    #[instrument(skip_all, fields(position = ?self.position, model = %ctx.model_name, base_api = %ctx.base_api, memory = self.memory.len(), neighbors = neighbors.len()))]
+    /// Computes the next entry on the first resolver of `route`, moving on to
+    /// the next one whenever a resolver fails. If the first resolver has a
+    /// hedge threshold and is slower than it, the second one is asked as well
+    /// and the first valid answer is kept.
+    pub async fn calculate_next_routed(
+        &self,
+        route: &[CognitiveContext],
+        neighbors: Vec<CognitiveUnitPair>,
+    ) -> CognitiveUnitComplex {
+        let (primary, rest) = route.split_first().expect("route has no resolver");
+        let mut rest = rest.iter();
+        let mut attempts = vec![];
+        let mut hedged_to = None;
+
+        let mut result = match (primary.policy.hedge_after(), rest.as_slice().first()) {
+            (Some(after), Some(hedge)) => {
+                let (result, hedged) = self
+                    .hedged_attempt(primary, hedge, after, &neighbors, &mut attempts)
+                    .await;
+
+                // A hedge that was not sent is still there to fail over to.
+                if hedged {
+                    rest.next();
+                    hedged_to = Some(hedge.resolver_id.clone());
+                }
+
+                result
+            }
+            _ => self.attempt(primary, &neighbors).await,
+        };
+
+        while !result.outcome.is_success() {
+            let Some(next) = rest.next() else {
+                break;
+            };
+
+            debug!(
+                position = ?self.position,
+                from = result.resolver_id.as_deref().unwrap_or_default(),
+                to = %next.resolver_id,
+                outcome = ?result.outcome,
+                "unit_failover"
+            );
+
+            attempts.push(ResolverAttempt::new(&result));
+            result = self.attempt(next, &neighbors).await;
+        }
+
+        CognitiveUnitComplex {
+            attempts,
+            hedged_to,
+            ..result
+        }
+    }
+
+    /// Runs `primary` and, if it has not answered after `after`, `hedge`
+    /// alongside it. Returns the first valid answer, or the last one if
+    /// both fail, and whether the hedge was sent. An unfinished call is
+    /// dropped.
+    async fn hedged_attempt(
+        &self,
+        primary: &CognitiveContext,
+        hedge: &CognitiveContext,
+        after: Duration,
+        neighbors: &[CognitiveUnitPair],
+        attempts: &mut Vec<ResolverAttempt>,
+    ) -> (CognitiveUnitComplex, bool) {
+        let first = self.attempt(primary, neighbors);
+        tokio::pin!(first);
+
+        if let Ok(result) = tokio::time::timeout(after, &mut first).await {
+            return (result, false);
+        }
+
+        debug!(
+            position = ?self.position,
+            from = %primary.resolver_id,
+            to = %hedge.resolver_id,
+            after_ms = after.as_millis() as u64,
+            "unit_hedged"
+        );
+
+        let second = self.attempt(hedge, neighbors);
+        tokio::pin!(second);
+
+        let (result, other) = match select(first, second).await {
+            Either::Left((result, other)) => (result, other),
+            Either::Right((result, other)) => (result, other),
+        };
+
+        if result.outcome.is_success() {
+            return (result, true);
+        }
+
+        attempts.push(ResolverAttempt::new(&result));
+
+        (other.await, true)
+    }
+
+    /// One call on `ctx`, fed to its circuit breaker and throughput.
+    async fn attempt(
+        &self,
+        ctx: &CognitiveContext,
+        neighbors: &[CognitiveUnitPair],
+    ) -> CognitiveUnitComplex {
+        let result = self.calculate_next_complex(ctx, neighbors.to_vec()).await;
+
+        // The resolver was not asked, so there is nothing to learn about it.
+        if result.cached {
+            return result;
+        }
+
+        ctx.guard
+            .observe(result.latency_ms, result.outcome.is_success());
+
+        if ctx
+            .guard
+            .record(&ctx.policy, result.outcome.is_llm_failure())
+        {
+            warn!(
+                resolver_id = %ctx.resolver_id,
+                cooldown_secs = ctx.policy.circuit_breaker.map(|breaker| breaker.cooldown_secs),
+                "resolver_circuit_opened"
+            );
+        }
+
+        result
+    }
+
+    #[instrument(skip_all, fields(position = ?self.position, resolver_id = %ctx.resolver_id, model = %ctx.model_name, base_api = %ctx.base_api, memory = self.memory.len(), neighbors = neighbors.len(), input_tokens = field::Empty, output_tokens = field::Empty, latency_ms = field::Empty, feedback = field::Empty, otel.status_code = field::Empty))]
     pub async fn calculate_next_complex(
         &self,
         ctx: &CognitiveContext,
@@ -121,25 +450,81 @@ impl CognitiveUnitWithMemory {
         ]
         .join(".\n");
 
-        let structured =
-            match Self::rig_structured_completion(ctx, &system_message, &input_payload).await {
-                Ok(structured) => structured,
-                Err(err) => {
-                    let feedback = classify_rig_error(&err.to_string());
-
-                    warn!(
-                        model = %ctx.model_name,
-                        base_api = %ctx.base_api,
-                        provider = ?ctx.provider,
-                        position = ?self.position,
-                        error = %err,
+        let cache_key = ctx.cache.as_ref().map(|_| {
+            ResponseCache::key(
+                &ctx.resolver_id,
+                &ctx.model_name,
+                &system_message,
+                &input_payload,
+            )
+        });
+
+        if let Some((cache, key)) = ctx.cache.as_ref().zip(cache_key.as_ref()) {
+            if let Some(pair) = cache.get(key) {
+                debug!(
+                    position = ?self.position,
+                    resolver_id = %ctx.resolver_id,
+                    key = %key,
+                    "llm_response_cache_hit"
+                );
+
+                return CognitiveUnitComplex {
+                    timestamp: Utc::now(),
+                    rule: pair.rule,
+                    state: pair.state,
+                    neighbors: neighbors.iter().map(|n| n.state.clone()).collect(),
+                    feedback: "".to_string(),
+                    tokens: 0,
+                    input_tokens: 0,
+                    output_tokens: 0,
+                    resolver_id: Some(ctx.resolver_id.clone()),
+                    latency_ms: started_at.elapsed().as_millis() as u64,
+                    outcome: UnitOutcome::Ok,
+                    attempts: vec![],
+                    hedged_to: None,
+                    cached: true,
+                };
+            }
+        }
+
+        let completion = Self::validated_completion(ctx, &system_message, &input_payload).await;
+        let latency_ms = started_at.elapsed().as_millis() as u64;
+        let span = Span::current();
+        span.record("latency_ms", latency_ms);
+
+        let (structured, outcome) = match completion {
+            Ok(completion) => completion,
+            Err(failure) => {
+                let feedback = failure.feedback();
+
+                warn!(
+                    model = %ctx.model_name,
+                    base_api = %ctx.base_api,
+                    provider = ?ctx.provider,
+                    position = ?self.position,
+                    outcome = ?failure.outcome,
+                    error = %failure.error,
                     feedback = %feedback,
-                        "llm_request_failed"
-                    );
+                    "llm_request_failed"
+                );
+
+                span.record("feedback", feedback.as_str());
+                span.record("otel.status_code", "ERROR");
+
+                return CognitiveUnitComplex {
+                    resolver_id: Some(ctx.resolver_id.clone()),
+                    latency_ms,
+                    outcome: failure.outcome,
+                    tokens: failure.spent.total_tokens,
+                    input_tokens: failure.spent.input_tokens,
+                    output_tokens: failure.spent.output_tokens,
+                    ..self.fallback_complex(&neighbors, feedback)
+                };
+            }
+        };
 
-                    return self.fallback_complex(&neighbors, feedback);
-                }
-            };
+        span.record("input_tokens", structured.input_tokens);
+        span.record("output_tokens", structured.output_tokens);
 
         if tracing::enabled!(target: "llmca::model_response", tracing::Level::DEBUG) {
             debug!(
@@ -163,16 +548,29 @@ impl CognitiveUnitWithMemory {
             prompt_tokens = structured.input_tokens,
             completion_tokens = structured.output_tokens,
             total_tokens = structured.total_tokens,
-            elapsed_ms = started_at.elapsed().as_millis() as u64,
+            elapsed_ms = latency_ms,
             "llm_request_completed"
         );
 
+        if let Some((cache, key)) = ctx.cache.as_ref().zip(cache_key.as_ref()) {
+            cache.put(key, &structured.pair);
+        }
+
         CognitiveUnitComplex {
             timestamp: Utc::now(),
             rule: structured.pair.rule,
             state: structured.pair.state,
             neighbors: neighbors.iter().map(|n| n.state.clone()).collect(),
             feedback: "".to_string(),
+            tokens: structured.total_tokens,
+            input_tokens: structured.input_tokens,
+            output_tokens: structured.output_tokens,
+            resolver_id: Some(ctx.resolver_id.clone()),
+            latency_ms,
+            outcome,
+            attempts: vec![],
+            hedged_to: None,
+            cached: false,
         }
     }
 
@@ -189,6 +587,123 @@ impl CognitiveUnitWithMemory {
             state: previous.state,
             neighbors: neighbors.iter().map(|n| n.state.clone()).collect(),
             feedback,
+            tokens: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            resolver_id: None,
+            latency_ms: 0,
+            outcome: UnitOutcome::Ok,
+            attempts: vec![],
+            hedged_to: None,
+            cached: false,
+        }
+    }
+
+    /// Asks for a pair until one passes [`validate_pair`], retrying schema
+    /// violations and rejections up to [`STRUCTURED_OUTPUT_RETRIES`] times.
+    /// Tokens of rejected answers are added to the result.
+    async fn validated_completion(
+        ctx: &CognitiveContext,
+        system_message: &str,
+        user_message: &str,
+    ) -> Result<(StructuredCompletion, UnitOutcome), CompletionFailure> {
+        let mut spent = TokenUsage::default();
+        let mut attempt = 0;
+
+        loop {
+            let failure = match Self::guarded_completion(ctx, system_message, user_message).await {
+                Ok((mut structured, retried)) => match validate_pair(&structured.pair) {
+                    Ok(()) => {
+                        structured.add(&spent);
+
+                        let outcome = match attempt == 0 && !retried {
+                            true => UnitOutcome::Ok,
+                            false => UnitOutcome::RetriedOk,
+                        };
+
+                        return Ok((structured, outcome));
+                    }
+                    Err(reason) => {
+                        spent.add(&structured.usage());
+
+                        CompletionFailure::new(UnitOutcome::ValidationRejected, reason.into())
+                    }
+                },
+                Err(failure) => failure,
+            };
+
+            let retryable = matches!(
+                failure.outcome,
+                UnitOutcome::SchemaViolation | UnitOutcome::ValidationRejected
+            );
+
+            if !retryable || attempt == STRUCTURED_OUTPUT_RETRIES {
+                return Err(CompletionFailure { spent, ..failure });
+            }
+
+            attempt += 1;
+
+            debug!(
+                model = %ctx.model_name,
+                outcome = ?failure.outcome,
+                error = %failure.error,
+                attempt,
+                "llm_structured_output_retry"
+            );
+        }
+    }
+
+    /// Runs a completion under the resolver's policy: waits for its rate
+    /// limits, abandons it after its timeout and retries transport errors and
+    /// timeouts with exponential backoff. Tells whether it had to retry.
+    async fn guarded_completion(
+        ctx: &CognitiveContext,
+        system_message: &str,
+        user_message: &str,
+    ) -> Result<(StructuredCompletion, bool), CompletionFailure> {
+        let mut attempt = 0;
+
+        loop {
+            ctx.guard.acquire(&ctx.policy).await;
+
+            let completion = Self::rig_structured_completion(ctx, system_message, user_message);
+
+            let result = match ctx.policy.timeout() {
+                Some(timeout) => tokio::time::timeout(timeout, completion)
+                    .await
+                    .unwrap_or_else(|elapsed| {
+                        Err(CompletionFailure::new(UnitOutcome::Timeout, elapsed.into()))
+                    }),
+                None => completion.await,
+            };
+
+            let failure = match result {
+                Ok(structured) => {
+                    ctx.guard.spend(structured.total_tokens);
+
+                    return Ok((structured, attempt > 0));
+                }
+                Err(failure) => failure,
+            };
+
+            if !failure.outcome.is_llm_failure() || attempt == ctx.policy.retries {
+                return Err(failure);
+            }
+
+            attempt += 1;
+
+            let backoff = ctx.policy.backoff(attempt);
+
+            debug!(
+                model = %ctx.model_name,
+                outcome = ?failure.outcome,
+                error = %failure.error,
+                attempt,
+                backoff_ms = backoff.as_millis() as u64,
+                "llm_request_retry"
+            );
+
+            tokio::time::sleep(backoff).await;
         }
     }
 
@@ -197,7 +712,7 @@ impl CognitiveUnitWithMemory {
         ctx: &CognitiveContext,
         system_message: &str,
         user_message: &str,
-    ) -> Result<StructuredCompletion, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<StructuredCompletion, CompletionFailure> {
         match ctx.provider {
             LLMProvider::Ollama => {
                 Self::ollama_structured_completion(ctx, system_message, user_message).await
@@ -212,8 +727,8 @@ impl CognitiveUnitWithMemory {
         ctx: &CognitiveContext,
         system_message: &str,
         user_message: &str,
-    ) -> Result<StructuredCompletion, Box<dyn std::error::Error + Send + Sync>> {
-        let client = build_ollama_client(ctx)?;
+    ) -> Result<StructuredCompletion, CompletionFailure> {
+        let client = build_ollama_client(ctx).map_err(CompletionFailure::transport)?;
         let agent = client
             .agent(&ctx.model_name)
             .preamble(system_message)
@@ -224,7 +739,8 @@ impl CognitiveUnitWithMemory {
             .prompt_typed::<CognitiveUnitPair>(user_message.to_string())
             .max_turns(1)
             .extended_details()
-            .await?;
+            .await
+            .map_err(|err| CompletionFailure::classify(err.into()))?;
 
         Ok(StructuredCompletion::new(response.output, response.usage))
     }
@@ -233,22 +749,25 @@ impl CognitiveUnitWithMemory {
         ctx: &CognitiveContext,
         system_message: &str,
         user_message: &str,
-    ) -> Result<StructuredCompletion, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<StructuredCompletion, CompletionFailure> {
         let client = openrouter::Client::builder()
             .api_key(ctx.secret_key.as_str())
             .base_url(ctx.base_api.as_str())
-            .build()?;
+            .build()
+            .map_err(|err| CompletionFailure::transport(err.into()))?;
 
         let model = client.completion_model(&ctx.model_name).with_strict_tools();
         let extractor = ExtractorBuilder::<_, CognitiveUnitPair>::new(model)
             .preamble(system_message)
             .max_tokens(512)
-            .retries(1)
+            // Retried by `validated_completion`, which can tell that it did.
+            .retries(0)
             .build();
 
         let response = extractor
             .extract_with_usage(user_message.to_string())
-            .await?;
+            .await
+            .map_err(|err| CompletionFailure::classify(err.into()))?;
 
         Ok(StructuredCompletion::new(response.data, response.usage))
     }
@@ -270,6 +789,121 @@ impl StructuredCompletion {
             total_tokens: usage.total_tokens,
         }
     }
+
+    fn usage(&self) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+            total_tokens: self.total_tokens,
+        }
+    }
+
+    fn add(&mut self, usage: &TokenUsage) {
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.total_tokens += usage.total_tokens;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct TokenUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+    total_tokens: u64,
+}
+
+impl TokenUsage {
+    fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Why a resolver call produced no usable pair, and the tokens it spent.
+struct CompletionFailure {
+    outcome: UnitOutcome,
+    error: BoxError,
+    spent: TokenUsage,
+}
+
+impl CompletionFailure {
+    fn new(outcome: UnitOutcome, error: BoxError) -> Self {
+        Self {
+            outcome,
+            error,
+            spent: TokenUsage::default(),
+        }
+    }
+
+    fn transport(error: BoxError) -> Self {
+        Self::new(UnitOutcome::TransportError, error)
+    }
+
+    /// Classifies an error returned by a rig call by the errors in its source
+    /// chain: JSON errors and answers without any structured data mean the
+    /// answer violated the schema, timed-out I/O or HTTP errors a timeout, and
+    /// anything else a transport error.
+    fn classify(error: BoxError) -> Self {
+        let mut source: Option<&(dyn Error + 'static)> = Some(error.as_ref());
+
+        while let Some(err) = source {
+            let no_data = err.is::<serde_json::Error>()
+                || matches!(
+                    err.downcast_ref::<ExtractionError>(),
+                    Some(ExtractionError::NoData)
+                )
+                || matches!(
+                    err.downcast_ref::<StructuredOutputError>(),
+                    Some(StructuredOutputError::EmptyResponse)
+                );
+
+            if no_data {
+                return Self::new(UnitOutcome::SchemaViolation, error);
+            }
+
+            let timed_out = err.is::<tokio::time::error::Elapsed>()
+                || err
+                    .downcast_ref::<reqwest::Error>()
+                    .is_some_and(reqwest::Error::is_timeout)
+                || err
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|err| err.kind() == std::io::ErrorKind::TimedOut);
+
+            if timed_out {
+                return Self::new(UnitOutcome::Timeout, error);
+            }
+
+            source = err.source();
+        }
+
+        Self::transport(error)
+    }
+
+    /// What the unit is left with as feedback.
+    fn feedback(&self) -> String {
+        match self.outcome {
+            UnitOutcome::SchemaViolation => format!("Structured output failed: {}", self.error),
+            UnitOutcome::ValidationRejected => format!("Output rejected: {}", self.error),
+            UnitOutcome::Timeout => format!("LLM request timed out: {}", self.error),
+            _ => format!("LLM request failed: {}", self.error),
+        }
+    }
+}
+
+/// Rejects pairs that parse but cannot be used as a unit's next entry.
+pub fn validate_pair(pair: &CognitiveUnitPair) -> Result<(), String> {
+    if pair.state.trim().is_empty() {
+        return Err("empty state".to_string());
+    }
+
+    if pair.rule.trim().is_empty() {
+        return Err("empty rule".to_string());
+    }
+
+    Ok(())
 }
 
 fn build_ollama_client(
@@ -293,7 +927,7 @@ fn build_ollama_client(
     Ok(client)
 }
 
-fn normalize_ollama_base_url(api_url: &str) -> String {
+pub(crate) fn normalize_ollama_base_url(api_url: &str) -> String {
     let trimmed = api_url.trim_end_matches('/');
 
     if let Some(base) = trimmed.strip_suffix("/api/v1") {
@@ -305,13 +939,71 @@ fn normalize_ollama_base_url(api_url: &str) -> String {
     }
 }
 
-fn classify_rig_error(error: &str) -> String {
-    if error.contains("DeserializationError")
-        || error.contains("No data extracted")
-        || error.contains("EmptyResponse")
-    {
-        format!("Structured output failed: {error}")
-    } else {
-        format!("LLM request failed: {error}")
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(extra: &str) -> CognitiveUnitComplex {
+        serde_json::from_str(&format!(
+            r#"{{"timestamp":"2025-01-01T00:00:00Z","rule":"r","state":"s","neighbors":[]{extra}}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn legacy_entries_get_their_outcome_from_the_feedback() {
+        let outcome = |feedback: &str| entry(&format!(r#","feedback":"{feedback}""#)).outcome;
+
+        assert_eq!(outcome(""), UnitOutcome::Ok);
+        assert_eq!(outcome("please turn blue"), UnitOutcome::Ok);
+        assert_eq!(
+            outcome("LLM request failed: connection refused"),
+            UnitOutcome::TransportError
+        );
+        assert_eq!(
+            outcome("LLM request failed: request timed out"),
+            UnitOutcome::Timeout
+        );
+        assert_eq!(
+            outcome("Structured output failed: No data extracted"),
+            UnitOutcome::SchemaViolation
+        );
+    }
+
+    #[test]
+    fn recorded_outcomes_win_over_the_feedback() {
+        let unit = entry(r#","feedback":"LLM request failed: x","outcome":"retried_ok""#);
+
+        assert_eq!(unit.outcome, UnitOutcome::RetriedOk);
+    }
+
+    #[test]
+    fn classify_reads_rig_error_variants() {
+        let outcome = |error: BoxError| CompletionFailure::classify(error).outcome;
+
+        assert_eq!(
+            outcome(ExtractionError::NoData.into()),
+            UnitOutcome::SchemaViolation
+        );
+        assert_eq!(
+            outcome(StructuredOutputError::EmptyResponse.into()),
+            UnitOutcome::SchemaViolation
+        );
+        assert_eq!(
+            outcome(
+                serde_json::from_str::<CognitiveUnitPair>("{")
+                    .unwrap_err()
+                    .into()
+            ),
+            UnitOutcome::SchemaViolation
+        );
+        assert_eq!(
+            outcome(std::io::Error::from(std::io::ErrorKind::TimedOut).into()),
+            UnitOutcome::Timeout
+        );
+        assert_eq!(
+            outcome(std::io::Error::other("connection refused").into()),
+            UnitOutcome::TransportError
+        );
     }
 }
@@ -0,0 +1,130 @@
+//! Runs a step with span export pointed at a local collector and checks that
+//! the step's span and its units' child spans reach it.
+
+#![cfg(feature = "otlp")]
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use dynamical_system::system::{
+    observability::{init_tracing, LogFormat, OTLP_ENDPOINT_ENV},
+    space::{build_lattice_with_memory, LLMResolver},
+    unit::LLMProvider,
+    unit_next::CognitiveUnitPair,
+};
+
+/// Bodies posted to `/v1/traces`.
+type Exports = Arc<Mutex<Vec<Vec<u8>>>>;
+
+/// Accepts trace exports on `/v1/traces` and fails every other request, so
+/// the resolver pointed at it errors out right away.
+fn serve(listener: TcpListener, exports: Exports) {
+    for stream in listener.incoming().flatten() {
+        let exports = exports.clone();
+
+        thread::spawn(move || handle(stream, exports));
+    }
+}
+
+fn handle(stream: TcpStream, exports: Exports) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+
+    loop {
+        let mut request_line = String::new();
+
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+
+        let mut content_length = 0;
+
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+
+            let header = header.trim_end();
+
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+
+        let status = if request_line.contains(" /v1/traces ") {
+            exports.lock().unwrap().push(body);
+            "200 OK"
+        } else {
+            "500 Internal Server Error"
+        };
+
+        let response = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n");
+
+        if stream.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn count(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|window| *window == needle)
+        .count()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn step_and_unit_spans_reach_the_collector() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let exports = Exports::default();
+
+    thread::spawn({
+        let exports = exports.clone();
+        move || serve(listener, exports)
+    });
+
+    std::env::set_var(OTLP_ENDPOINT_ENV, &endpoint);
+    std::env::set_var("RUST_LOG", "info");
+
+    let guard = init_tracing("llmca-otlp-test", LogFormat::Json);
+
+    let resolvers = vec![LLMResolver::new(
+        Some("local".to_string()),
+        endpoint.clone(),
+        "_".to_string(),
+        "test-model".to_string(),
+        Some(LLMProvider::Ollama),
+    )];
+
+    let mut space = build_lattice_with_memory(1, 2, 2, |_| CognitiveUnitPair {
+        rule: "keep your state".to_string(),
+        state: "#000000".to_string(),
+    });
+
+    let telemetry = space.distributed_step(&resolvers).await;
+    assert_eq!(telemetry.units_total, 2);
+
+    // Flushes the batch exporter.
+    tokio::task::spawn_blocking(move || drop(guard))
+        .await
+        .unwrap();
+
+    let exported = exports.lock().unwrap().concat();
+
+    assert!(!exported.is_empty(), "nothing was exported");
+    assert_eq!(count(&exported, b"distributed_step_with"), 1);
+    assert_eq!(count(&exported, b"calculate_next_complex"), 2);
+}
@@ -1,12 +1,16 @@
 use dotenv::dotenv;
 use dynamical_system::{
-    life::{entity::Entity, manager::LifeManager},
-    system::unit_next::CognitiveUnitPair,
+    life::{config::LifeManagerConfig, entity::Entity, manager::LifeManager},
+    system::{
+        metrics::{serve_exporter, METRICS_ADDR_ENV},
+        observability::{init_tracing, LogFormat},
+        render::state_rgb,
+        unit_next::CognitiveUnitPair,
+    },
 };
 use itertools::Itertools;
 use macroquad::prelude::*;
-use std::time::Instant;
-use tracing_subscriber::EnvFilter;
+use std::{net::SocketAddr, time::Instant};
 
 fn window_conf() -> Conf {
     Conf {
@@ -20,13 +24,19 @@ fn window_conf() -> Conf {
 #[macroquad::main(window_conf)]
 async fn main() {
     dotenv().ok();
-    init_tracing();
+    let _tracing = init_tracing("llmca-minimal-ui", LogFormat::Pretty);
+    init_metrics();
     let startup_started_at = Instant::now();
 
     let rt = tokio::runtime::Runtime::new().unwrap();
 
     let manager_started_at = Instant::now();
-    let manager = LifeManager::without_loaded_entities();
+    let manager = LifeManagerConfig::from_env_and_args()
+        .and_then(|config| LifeManager::with_config(config.with_load_entities(false)))
+        .unwrap_or_else(|err| {
+            tracing::error!(error = %err, "minimal_ui_config_invalid");
+            std::process::exit(2);
+        });
     tracing::info!(
         elapsed_ms = manager_started_at.elapsed().as_millis() as u64,
         "minimal_ui_manager_ready"
@@ -46,7 +56,11 @@ async fn main() {
     let temporal_memory_size = 4;
 
     let entity_started_at = Instant::now();
-    let mut entity = Entity::new_2d_lattice(&manager, initial_state, size, temporal_memory_size);
+    let mut entity = Entity::new_2d_lattice(&manager, initial_state, size, temporal_memory_size)
+        .unwrap_or_else(|err| {
+            tracing::error!(error = %err, "minimal_ui_entity_failed");
+            std::process::exit(1);
+        });
     tracing::info!(
         entity_id = entity.id(),
         size_x = size.0,
@@ -92,33 +106,33 @@ async fn main() {
 
         next_frame().await;
 
-        let telemetry = rt.block_on(async { entity.evolve_async().await });
-
-        tracing::debug!(?telemetry, "minimal_ui_evolved_frame");
+        match rt.block_on(async { entity.evolve_async().await }) {
+            Ok(telemetry) => tracing::debug!(?telemetry, "minimal_ui_evolved_frame"),
+            Err(err) => tracing::error!(error = %err, "minimal_ui_evolve_failed"),
+        }
     }
 }
 
-fn init_tracing() {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-
-    let _ = tracing_subscriber::fmt()
-        .pretty()
-        .with_env_filter(filter)
-        .with_target(true)
-        // .json()
-        .try_init();
+/// Serves the step metrics on `LLMCA_METRICS_ADDR` for Prometheus to scrape,
+/// if it is set.
+fn init_metrics() {
+    let Ok(addr) = std::env::var(METRICS_ADDR_ENV) else {
+        return;
+    };
+
+    let started = match addr.parse::<SocketAddr>() {
+        Ok(socket) => serve_exporter(socket).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+
+    match started {
+        Ok(()) => tracing::info!(addr, "minimal_ui_metrics_exporter_started"),
+        Err(error) => tracing::warn!(addr, error, "minimal_ui_metrics_exporter_failed"),
+    }
 }
 
 fn get_color_from_hex_string(hex: &str) -> Color {
-    let hex = hex.trim_matches(['#', '\"', '[', ']']).to_lowercase();
-
-    if hex.len() < 6 {
-        return Color::new(0.0, 0.0, 0.0, 1.0);
-    }
-
-    let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-    let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-    let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
+    let [r, g, b] = state_rgb(hex);
 
-    Color::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0)
+    Color::from_rgba(r, g, b, 255)
 }
@@ -1,21 +1,36 @@
-use std::sync::Arc;
+mod remote;
+
+use std::{env, sync::Arc};
 
 use dotenvy::dotenv;
 use dynamical_system::{
     life::{
+        bundle::{export_entity, BUNDLE_EXTENSION},
+        config::LifeManagerConfig,
         entity::{Entity, EntityState},
+        intervention::InterventionRecord,
         manager::LifeManager,
     },
-    system::{space::load_llm_resolvers_from_toml, unit_next::CognitiveUnitPair},
+    system::{
+        observability::{init_tracing, LogFormat},
+        render::state_rgb,
+        unit_next::CognitiveUnitPair,
+    },
 };
 use eframe::egui::{self, CornerRadius, Frame, Margin, Sense, Slider, UiBuilder, Vec2};
 use itertools::Itertools;
 use tokio::sync::Mutex;
-use tracing_subscriber::EnvFilter;
+
+use crate::remote::RemoteApp;
+
+/// Base URL of a server API, e.g. `http://localhost:8000/api`. When set, the
+/// observatory browses that server instead of the local root folder.
+const SERVER_URL_ENV: &str = "LLMCA_SERVER_URL";
+const API_KEY_ENV: &str = "LLMCA_API_KEY";
 
 fn main() -> eframe::Result {
     dotenv().ok();
-    init_tracing();
+    let _tracing = init_tracing("llmca-observatory", LogFormat::Json);
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([1024.0, 1024.0]),
@@ -24,7 +39,17 @@ fn main() -> eframe::Result {
     eframe::run_native(
         "My egui App",
         options,
-        Box::new(|_cc| Ok(Box::<LifeManagerApp>::default())),
+        Box::new(|cc| match env::var(SERVER_URL_ENV) {
+            Ok(url) => {
+                let client = match env::var(API_KEY_ENV) {
+                    Ok(api_key) => client::Client::new(url).with_api_key(api_key),
+                    Err(_) => client::Client::new(url),
+                };
+
+                Ok(Box::new(RemoteApp::new(client, &cc.egui_ctx)) as Box<dyn eframe::App>)
+            }
+            Err(_) => Ok(Box::<LifeManagerApp>::default()),
+        }),
     )
 }
 
@@ -34,27 +59,33 @@ struct LifeManagerApp {
     selected_entity: usize,
     loaded_entity: Option<Arc<Mutex<Entity>>>,
     current_step: usize,
+    /// Intervention of the step on screen, keyed by entity id and step so
+    /// its sidecar is read once per selection rather than once per frame.
+    intervention: Option<(String, usize, Option<InterventionRecord>)>,
     runtime: tokio::runtime::Runtime,
     // worker_state: WorkerState,
 }
 
 impl Default for LifeManagerApp {
     fn default() -> Self {
-        let mut manager = LifeManager::default();
-        let resolvers = load_llm_resolvers_from_toml("resolvers.toml");
+        let manager = LifeManagerConfig::from_env_and_args()
+            .and_then(LifeManager::with_config)
+            .unwrap_or_else(|err| {
+                tracing::error!(error = %err, "observatory_config_invalid");
+                std::process::exit(2);
+            });
 
         tracing::info!(
-            resolver_count = resolvers.len(),
+            resolver_count = manager.resolvers().len(),
             "observatory_loaded_resolvers"
         );
 
-        manager.set_resolvers(resolvers);
-
         Self {
             life_manager: manager,
             selected_entity: 0,
             loaded_entity: None,
             current_step: 0,
+            intervention: None,
             runtime: tokio::runtime::Runtime::new().unwrap(),
         }
     }
@@ -136,8 +167,6 @@ impl eframe::App for LifeManagerApp {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("Entities", |ui| {
                     if ui.button("New").clicked() {
-                        let manager = LifeManager::default();
-
                         let size = (3, 3);
 
                         let initial_state = (0..size.0)
@@ -153,10 +182,10 @@ impl eframe::App for LifeManagerApp {
 
                         let temporal_memory_size = 4;
 
-                        let entity =
-                            Entity::new_2d_lattice(&manager, initial_state, size, temporal_memory_size);
-
-                        tracing::info!(entity_id = entity.id(), "observatory_entity_created");
+                        match Entity::new_2d_lattice(&self.life_manager, initial_state, size, temporal_memory_size) {
+                            Ok(entity) => tracing::info!(entity_id = entity.id(), "observatory_entity_created"),
+                            Err(err) => tracing::error!(error = %err, "observatory_entity_create_failed"),
+                        }
 
                     }
                 });
@@ -186,7 +215,7 @@ impl eframe::App for LifeManagerApp {
                     // .cloned();
                     self.loaded_entity = self
                         .life_manager
-                        .get_entity(&entities[self.selected_entity])
+                        .get_mut_entity(&entities[self.selected_entity])
                         .map(|e| Arc::new(Mutex::new(e.clone())));
 
                     self.current_step =
@@ -201,15 +230,59 @@ impl eframe::App for LifeManagerApp {
 
                     if ui.button("evolve").clicked() {
                         let entity_clone = Arc::clone(managed_entity);
-                        let handle = self.runtime.handle().clone();
                         let resolvers = self.life_manager.resolvers().to_owned();
 
                         self.runtime.spawn(async move {
-                            let telemetry = entity_clone.lock().await.evolve(&handle, &resolvers).await;
-                            tracing::debug!(?telemetry, "observatory_entity_evolved");
+                            match entity_clone.lock().await.evolve(&resolvers).await {
+                                Ok(telemetry) => tracing::debug!(?telemetry, "observatory_entity_evolved"),
+                                Err(err) => tracing::error!(error = %err, "observatory_evolve_failed"),
+                            }
                         });
                     }
 
+                    if ui.button("rewind here").clicked() {
+                        let mut entity = managed_entity.blocking_lock();
+
+                        match entity.rewind_to(self.current_step as u32) {
+                            Ok(archive) => tracing::info!(?archive, "observatory_entity_rewound"),
+                            Err(err) => {
+                                tracing::warn!(error = %err, "observatory_entity_rewind_failed")
+                            }
+                        }
+                    }
+
+                    if ui.button("export").clicked() {
+                        let entity = managed_entity.blocking_lock();
+                        let path = format!("{}.{}", entity.id(), BUNDLE_EXTENSION);
+
+                        match std::fs::File::create(&path)
+                            .and_then(|file| export_entity(&entity, file, true))
+                        {
+                            Ok(_) => tracing::info!(path, "observatory_entity_exported"),
+                            Err(err) => {
+                                tracing::warn!(error = %err, "observatory_entity_export_failed")
+                            }
+                        }
+                    }
+
+                    let latest_archive = managed_entity.blocking_lock().list_archives().pop();
+
+                    if let Some(archive) = latest_archive {
+                        if ui.button("undo rewind").clicked() {
+                            let mut entity = managed_entity.blocking_lock();
+
+                            match entity.restore_archive(&archive) {
+                                Ok(()) => {
+                                    self.current_step = entity.current_step() as usize;
+                                    tracing::info!(archive, "observatory_entity_archive_restored");
+                                }
+                                Err(err) => {
+                                    tracing::warn!(error = %err, "observatory_entity_restore_failed")
+                                }
+                            }
+                        }
+                    }
+
                     if let EntityState::ComputingStep(step) = managed_entity.blocking_lock().state(){
                         ui.label(format!("Computing step {}", step));
 
@@ -248,6 +321,28 @@ impl eframe::App for LifeManagerApp {
 
                 ui.label(format!("Step: {}", current_step));
 
+                let cached = matches!(
+                    &self.intervention,
+                    Some((id, step, _)) if id == entity.id() && *step == self.current_step
+                );
+
+                if !cached {
+                    self.intervention = Some((
+                        entity.id().to_string(),
+                        self.current_step,
+                        entity.intervention_at(self.current_step as u32),
+                    ));
+                }
+
+                if let Some((_, _, Some(intervention))) = &self.intervention {
+                    ui.label(format!(
+                        "Step {} edited by {} ({} units)",
+                        intervention.step,
+                        intervention.intervention.author,
+                        intervention.units_changed.len(),
+                    ));
+                }
+
                 // ui.label(format!("Space: {:?}", entity.space()));
 
                 let mut latest_p_y = 0;
@@ -291,10 +386,10 @@ impl eframe::App for LifeManagerApp {
                                             .sense(Sense::click() & Sense::hover()),
                                         |ui| {
                                             Frame::canvas(ui.style())
-                                                .fill(
-                                                    egui::Color32::from_hex(&color)
-                                                        .unwrap_or(egui::Color32::BLACK),
-                                                )
+                                                .fill({
+                                                    let [r, g, b] = state_rgb(&color);
+                                                    egui::Color32::from_rgb(r, g, b)
+                                                })
                                                 .corner_radius(CornerRadius::ZERO)
                                                 .inner_margin(Margin::ZERO)
                                                 .outer_margin(Margin::ZERO)
@@ -325,13 +420,3 @@ impl eframe::App for LifeManagerApp {
         });
     }
 }
-
-fn init_tracing() {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-
-    let _ = tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(true)
-        .json()
-        .try_init();
-}
@@ -0,0 +1,277 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use client::{state_rgb, Client, ClientError, EntitySummary, StepGrid};
+use eframe::egui::{self, CornerRadius, Frame, Margin, Sense, Slider, UiBuilder, Vec2};
+
+/// What the server last answered with. Requests run on the runtime and write
+/// here; the UI only reads.
+#[derive(Default)]
+struct RemoteState {
+    entities: Vec<String>,
+    summary: Option<EntitySummary>,
+    grid: Option<StepGrid>,
+    busy: bool,
+    error: Option<String>,
+}
+
+/// Browses and drives the entities of a remote server instead of the local
+/// root folder.
+pub struct RemoteApp {
+    client: Client,
+    runtime: tokio::runtime::Runtime,
+    state: Arc<Mutex<RemoteState>>,
+    selected_entity: usize,
+    current_step: u32,
+    shown_step: Option<u32>,
+}
+
+impl RemoteApp {
+    pub fn new(client: Client, ctx: &egui::Context) -> Self {
+        tracing::info!(server = client.base_url(), "observatory_remote_mode");
+
+        let app = Self {
+            client,
+            runtime: tokio::runtime::Runtime::new().unwrap(),
+            state: Arc::new(Mutex::new(RemoteState::default())),
+            selected_entity: 0,
+            current_step: 0,
+            shown_step: None,
+        };
+
+        app.refresh_entities(ctx);
+        app
+    }
+
+    /// Runs `task` on the runtime, showing its error, if any, once it ends.
+    fn spawn(
+        &self,
+        ctx: &egui::Context,
+        task: impl Future<Output = Result<(), ClientError>> + Send + 'static,
+    ) {
+        let state = self.state.clone();
+        let ctx = ctx.clone();
+
+        state.lock().unwrap().busy = true;
+
+        self.runtime.spawn(async move {
+            let result = task.await;
+
+            let mut state = state.lock().unwrap();
+            state.busy = false;
+            state.error = result.err().map(|err| err.to_string());
+
+            if let Some(error) = &state.error {
+                tracing::warn!(error, "observatory_remote_request_failed");
+            }
+
+            ctx.request_repaint();
+        });
+    }
+
+    fn refresh_entities(&self, ctx: &egui::Context) {
+        let client = self.client.clone();
+        let state = self.state.clone();
+
+        self.spawn(ctx, async move {
+            let entities = client.entities().await?.entities;
+            state.lock().unwrap().entities = entities;
+            Ok(())
+        });
+    }
+
+    /// Fetches the entity's summary and its lattice at `step`, or at its
+    /// current step when `step` is unset.
+    fn load(&self, ctx: &egui::Context, id: String, step: Option<u32>) {
+        let client = self.client.clone();
+        let state = self.state.clone();
+
+        self.spawn(ctx, async move {
+            let summary = client.summary(&id).await?;
+            let grid = client.step(&id, step.unwrap_or(summary.step)).await?;
+
+            let mut state = state.lock().unwrap();
+            state.summary = Some(summary);
+            state.grid = Some(grid);
+            Ok(())
+        });
+    }
+
+    fn evolve(&self, ctx: &egui::Context, id: String) {
+        let client = self.client.clone();
+        let state = self.state.clone();
+
+        self.spawn(ctx, async move {
+            let result = client.evolve(&id).await?;
+            tracing::debug!(step = result.step, "observatory_entity_evolved");
+
+            let summary = client.summary(&id).await?;
+            let grid = client.step(&id, result.step).await?;
+
+            let mut state = state.lock().unwrap();
+            state.summary = Some(summary);
+            state.grid = Some(grid);
+            Ok(())
+        });
+    }
+
+    fn rewind(&self, ctx: &egui::Context, id: String, step: u32) {
+        let client = self.client.clone();
+        let state = self.state.clone();
+
+        self.spawn(ctx, async move {
+            let result = client.rewind(&id, step).await?;
+            tracing::info!(archive = ?result.archive, "observatory_entity_rewound");
+
+            let summary = client.summary(&id).await?;
+            let grid = client.step(&id, result.step).await?;
+
+            let mut state = state.lock().unwrap();
+            state.summary = Some(summary);
+            state.grid = Some(grid);
+            Ok(())
+        });
+    }
+}
+
+impl eframe::App for RemoteApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let (entities, summary, grid, busy, error) = {
+            let state = self.state.lock().unwrap();
+
+            (
+                state.entities.clone(),
+                state.summary.clone(),
+                state.grid.clone(),
+                state.busy,
+                state.error.clone(),
+            )
+        };
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(format!("Life Manager ({})", self.client.base_url()));
+
+            if let Some(error) = &error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("refresh").clicked() {
+                    self.refresh_entities(ctx);
+                }
+
+                if busy {
+                    ui.spinner();
+                }
+            });
+
+            if entities.is_empty() {
+                ui.label("No entities found");
+                return;
+            }
+
+            egui::ComboBox::from_label("Select an organism").show_index(
+                ui,
+                &mut self.selected_entity,
+                entities.len(),
+                |i| entities[i].clone(),
+            );
+
+            ui.horizontal(|ui| {
+                if busy {
+                    ui.disable();
+                }
+
+                if ui.button("load").clicked() {
+                    if let Some(id) = entities.get(self.selected_entity) {
+                        self.load(ctx, id.clone(), None);
+                    }
+                }
+
+                let Some(summary) = &summary else {
+                    return;
+                };
+
+                if ui.button("evolve").clicked() {
+                    self.evolve(ctx, summary.id.clone());
+                }
+
+                if ui.button("rewind here").clicked() {
+                    self.rewind(ctx, summary.id.clone(), self.current_step);
+                }
+
+                if let Some(step) = summary.computing_step {
+                    ui.label(format!("Computing step {step}"));
+                }
+            });
+
+            let (Some(summary), Some(grid)) = (summary, grid) else {
+                return;
+            };
+
+            ui.heading("Entity");
+            ui.label(format!("Step: {}", summary.step));
+
+            if let Some(author) = &grid.edited_by {
+                ui.label(format!("Step {} edited by {author}", grid.step));
+            }
+
+            // Follow the lattice when evolving or rewinding replaced it.
+            if self.shown_step != Some(grid.step) {
+                self.shown_step = Some(grid.step);
+                self.current_step = grid.step;
+            }
+
+            let slider =
+                ui.add_enabled(!busy, Slider::new(&mut self.current_step, 0..=summary.step));
+
+            if slider.drag_stopped() || (slider.changed() && !slider.dragged()) {
+                self.load(ctx, summary.id.clone(), Some(self.current_step));
+            }
+
+            let cell_size = 48.0;
+
+            ui.spacing_mut().item_spacing = Vec2::new(0.0, 0.0);
+
+            egui::Grid::new("entity")
+                .spacing(Vec2::new(0.0, 0.0))
+                .show(ui, |ui| {
+                    for x in 0..grid.width {
+                        for y in 0..grid.height {
+                            let state = &grid.states[x][y];
+                            let rule = &grid.rules[x][y];
+
+                            ui.scope_builder(
+                                UiBuilder::new()
+                                    .id_salt((x, y))
+                                    .sense(Sense::click() & Sense::hover()),
+                                |ui| {
+                                    Frame::canvas(ui.style())
+                                        .fill({
+                                            let [r, g, b] = state_rgb(state);
+                                            egui::Color32::from_rgb(r, g, b)
+                                        })
+                                        .corner_radius(CornerRadius::ZERO)
+                                        .inner_margin(Margin::ZERO)
+                                        .outer_margin(Margin::ZERO)
+                                        .show(ui, |ui| {
+                                            ui.set_width(cell_size);
+                                            ui.set_height(cell_size);
+                                        });
+                                },
+                            )
+                            .response
+                            .on_hover_text(format!(
+                                "pos: {:?}\nstate: {state}\nrule: {rule}",
+                                (x, y),
+                            ));
+                        }
+
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
@@ -0,0 +1,549 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env, fmt, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use dynamical_system::{
+    life::{intervention::DEFAULT_AUTHOR, storage::write_atomic},
+    system::telemetry::StepTelemetry,
+};
+use poem::{
+    http::{header, StatusCode},
+    Endpoint, IntoResponse, Request, Response,
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::registry::{EntityRegistry, RegistryError};
+
+pub const SERVER_CONFIG_ENV: &str = "LLMCA_SERVER_CONFIG";
+/// Set to `1`/`true` to let the server start without users, like `--no-auth`.
+pub const NO_AUTH_ENV: &str = "LLMCA_NO_AUTH";
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Entity owners, kept next to the entity folders.
+pub const OWNERS_FILE: &str = "owners.json";
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `server.toml`: where to listen, who may call the API and how much each
+/// caller may spend. Without users, authentication is disabled, which has to
+/// be asked for with `--no-auth` or [`NO_AUTH_ENV`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerConfig {
+    #[serde(default = "default_bind")]
+    pub bind: String,
+    /// CORS origins, `*` wildcards allowed.
+    #[serde(default = "default_allowed_origins")]
+    pub allowed_origins: Vec<String>,
+    #[serde(default)]
+    pub users: Vec<User>,
+    /// Whether the server may run without users.
+    #[serde(skip)]
+    pub no_auth: bool,
+}
+
+fn default_bind() -> String {
+    "0.0.0.0:8000".to_string()
+}
+
+fn default_allowed_origins() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: default_bind(),
+            allowed_origins: default_allowed_origins(),
+            users: vec![],
+            no_auth: false,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads the file named by `--server-config`, `LLMCA_SERVER_CONFIG` or
+    /// `server.toml`, in that order. A missing file gives the defaults. Fails
+    /// without users unless `--no-auth` or [`NO_AUTH_ENV`] is set.
+    /// Returns the arguments that are not the server's own, for
+    /// [`dynamical_system::life::config::LifeManagerConfig::with_args`].
+    pub fn from_env_and_args() -> io::Result<(Self, Vec<String>)> {
+        let mut path = env::var(SERVER_CONFIG_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("server.toml"));
+
+        let mut no_auth = env::var(NO_AUTH_ENV).is_ok_and(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "on" | "yes"
+            )
+        });
+
+        let mut args = env::args().skip(1);
+        let mut rest = vec![];
+
+        while let Some(arg) = args.next() {
+            if let Some(value) = arg.strip_prefix("--server-config=") {
+                path = PathBuf::from(value);
+            } else if arg == "--server-config" {
+                let value = args.next().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "--server-config needs a value")
+                })?;
+
+                path = PathBuf::from(value);
+            } else if arg == "--no-auth" {
+                no_auth = true;
+            } else {
+                rest.push(arg);
+            }
+        }
+
+        let config = Self {
+            no_auth,
+            ..Self::load(&path)?
+        };
+
+        if !config.no_auth && config.users.iter().all(|user| user.api_keys.is_empty()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} lists no users with API keys; add some, or pass --no-auth or set \
+                     {NO_AUTH_ENV}=1 to run without authentication",
+                    path.display()
+                ),
+            ));
+        }
+
+        Ok((config, rest))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let config: Self = toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        info!(
+            path = %path.display(),
+            users = config.users.len(),
+            "server_config_loaded"
+        );
+
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    #[serde(default, skip_serializing)]
+    pub api_keys: Vec<String>,
+    /// Admins see every entity, including ones without an owner.
+    #[serde(default)]
+    pub admin: bool,
+    #[serde(default)]
+    pub steps_per_hour: Option<u32>,
+    #[serde(default)]
+    pub tokens_per_day: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum QuotaError {
+    StepsPerHour(u32),
+    TokensPerDay(u64),
+}
+
+impl fmt::Display for QuotaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaError::StepsPerHour(limit) => write!(f, "quota of {limit} steps per hour reached"),
+            QuotaError::TokensPerDay(limit) => {
+                write!(f, "quota of {limit} tokens per day reached")
+            }
+        }
+    }
+}
+
+/// Steps and tokens a user spent recently.
+#[derive(Debug, Default)]
+struct Usage {
+    steps: VecDeque<Instant>,
+    tokens: VecDeque<(Instant, u64)>,
+}
+
+impl Usage {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .steps
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= HOUR)
+        {
+            self.steps.pop_front();
+        }
+
+        while self
+            .tokens
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= DAY)
+        {
+            self.tokens.pop_front();
+        }
+    }
+
+    fn tokens_last_day(&self) -> u64 {
+        self.tokens.iter().map(|(_, tokens)| tokens).sum()
+    }
+}
+
+/// API keys, entity ownership and quotas.
+///
+/// Usage is kept in memory, so quotas start over when the server restarts.
+/// Entity owners are saved to [`OWNERS_FILE`].
+#[derive(Debug)]
+pub struct Auth {
+    keys: HashMap<String, Arc<User>>,
+    anonymous: Arc<User>,
+    usage: Mutex<HashMap<String, Usage>>,
+    owners_path: PathBuf,
+    owners: RwLock<HashMap<String, String>>,
+}
+
+impl Auth {
+    pub fn new(users: Vec<User>, root_folder: &Path) -> Self {
+        let keys = users
+            .into_iter()
+            .map(Arc::new)
+            .flat_map(|user| {
+                user.api_keys
+                    .clone()
+                    .into_iter()
+                    .map(move |key| (key, user.clone()))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let owners_path = root_folder.join(OWNERS_FILE);
+
+        let owners = std::fs::read_to_string(&owners_path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        if keys.is_empty() {
+            warn!("server_auth_disabled");
+        }
+
+        Self {
+            keys,
+            anonymous: Arc::new(User {
+                name: DEFAULT_AUTHOR.to_string(),
+                api_keys: vec![],
+                admin: true,
+                steps_per_hour: None,
+                tokens_per_day: None,
+            }),
+            usage: Mutex::new(HashMap::new()),
+            owners_path,
+            owners: RwLock::new(owners),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// The user a request acts as: the owner of its `X-API-Key` or bearer
+    /// token, or an anonymous admin while authentication is disabled.
+    pub fn authenticate(&self, req: &Request) -> Option<Arc<User>> {
+        if !self.enabled() {
+            return Some(self.anonymous.clone());
+        }
+
+        let key = req.header(API_KEY_HEADER).or_else(|| {
+            req.header(header::AUTHORIZATION)
+                .and_then(|value| value.strip_prefix("Bearer "))
+        })?;
+
+        self.keys.get(key.trim()).cloned()
+    }
+
+    pub fn can_access(&self, user: &User, entity_id: &str) -> bool {
+        user.admin
+            || self
+                .owner_of(entity_id)
+                .is_some_and(|owner| owner == user.name)
+    }
+
+    pub fn owner_of(&self, entity_id: &str) -> Option<String> {
+        self.owners.read().unwrap().get(entity_id).cloned()
+    }
+
+    /// Records `user` as the owner of a new entity. Nothing is recorded while
+    /// authentication is disabled.
+    pub fn set_owner(&self, entity_id: &str, user: &User) -> io::Result<()> {
+        if !self.enabled() {
+            return Ok(());
+        }
+
+        let mut owners = self.owners.write().unwrap();
+        owners.insert(entity_id.to_string(), user.name.clone());
+
+        write_atomic(&self.owners_path, &serde_json::to_vec_pretty(&*owners)?)
+    }
+
+    /// Steps in the last hour and tokens in the last day spent by `user`.
+    pub fn usage(&self, user: &User) -> (usize, u64) {
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage.entry(user.name.clone()).or_default();
+
+        usage.prune(Instant::now());
+
+        (usage.steps.len(), usage.tokens_last_day())
+    }
+
+    /// Charges a step to `user`, unless that would exceed their quota, and
+    /// returns when it was charged. The tokens of a step are only known once
+    /// it ends, so the last step may go over `tokens_per_day`.
+    fn reserve_step(&self, user: &User) -> Result<Instant, QuotaError> {
+        let now = Instant::now();
+
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage.entry(user.name.clone()).or_default();
+
+        usage.prune(now);
+
+        if let Some(limit) = user.steps_per_hour {
+            if usage.steps.len() >= limit as usize {
+                return Err(QuotaError::StepsPerHour(limit));
+            }
+        }
+
+        if let Some(limit) = user.tokens_per_day {
+            if usage.tokens_last_day() >= limit {
+                return Err(QuotaError::TokensPerDay(limit));
+            }
+        }
+
+        usage.steps.push_back(now);
+
+        Ok(now)
+    }
+
+    /// Gives back the step reserved at `reserved_at`, which never started or
+    /// failed.
+    fn release_step(&self, user: &User, reserved_at: Instant) {
+        let mut usage = self.usage.lock().unwrap();
+
+        if let Some(usage) = usage.get_mut(&user.name) {
+            if let Some(index) = usage.steps.iter().position(|at| *at == reserved_at) {
+                usage.steps.remove(index);
+            }
+        }
+    }
+
+    fn record_tokens(&self, user: &User, tokens: u64) {
+        let mut usage = self.usage.lock().unwrap();
+
+        usage
+            .entry(user.name.clone())
+            .or_default()
+            .tokens
+            .push_back((Instant::now(), tokens));
+    }
+
+    /// Computes the next step of the entity on behalf of `user`, charging it
+    /// to their quota.
+    pub async fn evolve(
+        &self,
+        registry: &EntityRegistry,
+        user: &User,
+        entity_id: &str,
+    ) -> Result<StepTelemetry, RegistryError> {
+        let reserved_at = match self.reserve_step(user) {
+            Ok(reserved_at) => reserved_at,
+            Err(err) => {
+                warn!(user = %user.name, entity_id, error = %err, "user_quota_exceeded");
+
+                return Err(RegistryError::QuotaExceeded(err.to_string()));
+            }
+        };
+
+        let result = registry.evolve(entity_id).await;
+
+        match &result {
+            Ok(telemetry) => self.record_tokens(user, telemetry.total_tokens),
+            Err(_) => self.release_step(user, reserved_at),
+        }
+
+        result
+    }
+}
+
+/// Rejects requests without a valid API key with a 401 and hands the caller's
+/// [`User`] to the endpoints.
+pub async fn require_user<E: Endpoint>(
+    auth: Arc<Auth>,
+    next: Arc<E>,
+    mut req: Request,
+) -> poem::Result<Response> {
+    let Some(user) = auth.authenticate(&req) else {
+        return Ok(unauthenticated(&req));
+    };
+
+    req.extensions_mut().insert(user);
+
+    next.call(req).await.map(IntoResponse::into_response)
+}
+
+/// Like [`require_user`], but only lets admins through, for endpoints outside
+/// the API such as the metrics.
+pub async fn require_admin<E: Endpoint>(
+    auth: Arc<Auth>,
+    next: Arc<E>,
+    req: Request,
+) -> poem::Result<Response> {
+    let Some(user) = auth.authenticate(&req) else {
+        return Ok(unauthenticated(&req));
+    };
+
+    if !user.admin {
+        warn!(path = %req.uri().path(), user = %user.name, "request_forbidden");
+
+        return Ok(Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .content_type("application/json")
+            .body(json!({ "error": "admins only" }).to_string()));
+    }
+
+    next.call(req).await.map(IntoResponse::into_response)
+}
+
+fn unauthenticated(req: &Request) -> Response {
+    warn!(path = %req.uri().path(), "request_unauthenticated");
+
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(header::WWW_AUTHENTICATE, "Bearer")
+        .content_type("application/json")
+        .body(json!({ "error": "missing or invalid API key" }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, steps_per_hour: Option<u32>, tokens_per_day: Option<u64>) -> User {
+        User {
+            name: name.to_string(),
+            api_keys: vec![format!("{name}-key")],
+            admin: false,
+            steps_per_hour,
+            tokens_per_day,
+        }
+    }
+
+    fn auth(name: &str, users: Vec<User>) -> (Auth, PathBuf) {
+        let root = std::env::temp_dir().join(format!("llmca-auth-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        (Auth::new(users, &root), root)
+    }
+
+    #[test]
+    fn steps_per_hour_limits_reservations() {
+        let alice = user("alice", Some(2), None);
+        let (auth, root) = auth("steps", vec![alice.clone()]);
+
+        assert!(auth.reserve_step(&alice).is_ok());
+        assert!(auth.reserve_step(&alice).is_ok());
+        assert!(matches!(
+            auth.reserve_step(&alice),
+            Err(QuotaError::StepsPerHour(2))
+        ));
+        assert_eq!(auth.usage(&alice), (2, 0));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn failed_steps_are_given_back() {
+        let alice = user("alice", Some(2), None);
+        let (auth, root) = auth("release", vec![alice.clone()]);
+
+        let first = auth.reserve_step(&alice).unwrap();
+        auth.reserve_step(&alice).unwrap();
+        auth.release_step(&alice, first);
+
+        assert_eq!(auth.usage(&alice), (1, 0));
+        assert!(auth.reserve_step(&alice).is_ok());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn tokens_per_day_is_checked_before_the_step() {
+        let alice = user("alice", None, Some(100));
+        let (auth, root) = auth("tokens", vec![alice.clone()]);
+
+        auth.record_tokens(&alice, 99);
+        assert!(auth.reserve_step(&alice).is_ok());
+
+        auth.record_tokens(&alice, 1);
+        assert!(matches!(
+            auth.reserve_step(&alice),
+            Err(QuotaError::TokensPerDay(100))
+        ));
+        assert_eq!(auth.usage(&alice), (1, 100));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn prune_forgets_old_usage() {
+        let now = Instant::now();
+        let (Some(hour_ago), Some(day_ago)) = (now.checked_sub(HOUR), now.checked_sub(DAY)) else {
+            return;
+        };
+
+        let mut usage = Usage {
+            steps: VecDeque::from([hour_ago, now]),
+            tokens: VecDeque::from([(day_ago, 50), (hour_ago, 7)]),
+        };
+
+        usage.prune(now);
+
+        assert_eq!(usage.steps, [now]);
+        assert_eq!(usage.tokens_last_day(), 7);
+    }
+
+    #[test]
+    fn only_owners_and_admins_access_an_entity() {
+        let alice = user("alice", None, None);
+        let bob = user("bob", None, None);
+        let root_user = User {
+            admin: true,
+            ..user("root", None, None)
+        };
+        let (auth, root) = auth("owners", vec![alice.clone(), bob.clone()]);
+
+        auth.set_owner("e", &alice).unwrap();
+
+        assert!(auth.can_access(&alice, "e"));
+        assert!(!auth.can_access(&bob, "e"));
+        assert!(auth.can_access(&root_user, "e"));
+        assert_eq!(
+            Auth::new(vec![alice, bob], &root).owner_of("e").as_deref(),
+            Some("alice")
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
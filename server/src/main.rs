@@ -1,137 +1,1102 @@
-use std::sync::Arc;
+mod auth;
+mod models;
+mod registry;
+mod resolvers;
+mod runs;
 
-use dynamical_system::life::manager::LifeManager;
-use poem::{listener::TcpListener, middleware::Cors, EndpointExt, Route};
-use poem_openapi::{param::Path, payload::Json, OpenApi, OpenApiService};
-use serde_derive::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dotenv::dotenv;
+use dynamical_system::{
+    life::{
+        bundle::{export_entity, BUNDLE_EXTENSION},
+        config::LifeManagerConfig,
+        entity::Entity,
+        intervention::DEFAULT_AUTHOR,
+        manager::LifeManager,
+        run::RunStatus,
+    },
+    system::{
+        health::check_resolver,
+        metrics,
+        observability::{init_tracing, LogFormat},
+        render::{
+            encode_gif, encode_png, frame_pixels, DEFAULT_CELL_SIZE, DEFAULT_FPS,
+            MAX_ANIMATION_PIXELS, MAX_CELL_SIZE, MAX_FRAME_PIXELS,
+        },
+        space::LLMResolver,
+    },
+};
+use futures::StreamExt;
+use poem::{
+    endpoint::make_sync,
+    listener::TcpListener,
+    middleware::Cors,
+    web::{sse::Event, Data},
+    EndpointExt, Response, Route,
+};
+use poem_openapi::{
+    param::{Path, Query},
+    payload::{Attachment, AttachmentType, Binary, EventStream, Json},
+    types::ToJSON,
+    OpenApi, OpenApiService,
+};
+use tokio::sync::{broadcast::error::RecvError, OwnedMutexGuard};
 use tracing::info;
-use tracing_subscriber::EnvFilter;
+
+use crate::{
+    auth::{require_admin, require_user, Auth, ServerConfig, User, API_KEY_HEADER},
+    models::*,
+    registry::{EntityRegistry, RegistryError, SharedEntity},
+    resolvers::watch_resolvers,
+    runs::{RunError, RunManager},
+};
+
+const EVENTS_KEEP_ALIVE: Duration = Duration::from_secs(15);
+const TELEMETRY_PAGE_SIZE: usize = 50;
+const TELEMETRY_MAX_PAGE_SIZE: usize = 500;
+const ANIMATION_MAX_FRAMES: usize = 600;
 
 #[derive(Debug)]
 struct Api {
-    life_manager: Arc<LifeManager>,
+    registry: Arc<EntityRegistry>,
+    runs: Arc<RunManager>,
+    auth: Arc<Auth>,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct CognitiveUnitComplex {
-    pub rule: Option<String>,
-    pub state: Option<String>,          // in json format
-    pub neighbors: Option<Vec<String>>, // in json format
-    pub feedback: Option<String>,
-}
-
-// #[derive(Deserialize, Serialize)]
-// struct InteractionPayload {
-//     i: usize,
-//     j: usize,
-//     update_unit: CognitiveUnitComplex,
-// }
-
 #[OpenApi]
 impl Api {
     #[oai(path = "/life", method = "get")]
-    async fn life(&self) -> Json<Value> {
-        let entities = self.life_manager.list_entities();
+    async fn life(&self, user: Data<&Arc<User>>) -> Json<EntityList> {
+        let entities = self
+            .registry
+            .life_manager()
+            .read()
+            .await
+            .list_entities()
+            .into_iter()
+            .filter(|id| self.auth.can_access(&user, id))
+            .collect::<Vec<_>>();
 
-        Json(json!({ "entities": entities }))
+        Json(EntityList { entities })
     }
 
-    #[oai(path = "/life", method = "post")]
-    async fn create_life(&self, id: Json<String>) -> Json<Value> {
-        let entity = self.life_manager.register_entity(id.0);
+    /// The caller, their quotas and what they spent of them.
+    #[oai(path = "/me", method = "get")]
+    async fn me(&self, user: Data<&Arc<User>>) -> Json<Me> {
+        let (steps_last_hour, tokens_last_day) = self.auth.usage(&user);
 
-        Json(json!({ "entity": entity }))
+        Json(Me {
+            user: UserView {
+                name: user.name.clone(),
+                admin: user.admin,
+                steps_per_hour: user.steps_per_hour,
+                tokens_per_day: user.tokens_per_day,
+            },
+            auth_enabled: self.auth.enabled(),
+            usage: UsageView {
+                steps_last_hour,
+                tokens_last_day,
+            },
+        })
     }
 
-    #[oai(path = "/life/:id", method = "get")]
-    async fn get_life(&self, id: Path<String>) -> Json<Value> {
-        let entity = self.life_manager.get_entity(&id.0);
+    /// Registers an empty entity folder. Use `POST /entities` instead.
+    #[oai(path = "/life", method = "post", deprecated)]
+    async fn create_life(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Json<String>,
+    ) -> EntityResponse<RegisteredEntity> {
+        let registered = self
+            .registry
+            .life_manager()
+            .read()
+            .await
+            .try_register_entity(id.0.clone());
+
+        let entity = match registered {
+            Ok(entity) => entity,
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                return EntityResponse::conflict("entity already exists", None, None);
+            }
+            Err(err) => return err.into(),
+        };
+
+        if let Err(err) = self.auth.set_owner(&id.0, &user) {
+            return EntityResponse::Failed(error_body(err.to_string()));
+        }
+
+        EntityResponse::Ok(Json(RegisteredEntity {
+            entity: entity.display().to_string(),
+        }))
+    }
+
+    #[oai(path = "/entities", method = "post")]
+    async fn create_entity(
+        &self,
+        user: Data<&Arc<User>>,
+        payload: Json<CreateEntityPayload>,
+    ) -> CreateEntityResponse {
+        let spec = entity_spec(payload.0);
+
+        let entity = {
+            let mut life_manager = self.registry.life_manager().write().await;
+
+            life_manager
+                .create_entity(spec)
+                .map(|entity| entity.id().to_string())
+                .map(|id| life_manager.take_entity(&id).unwrap())
+        };
+
+        match entity {
+            Ok(entity) => {
+                if let Err(err) = self.auth.set_owner(entity.id(), &user) {
+                    return CreateEntityResponse::Failed(error_body(err.to_string()));
+                }
+
+                let response = CreatedEntity {
+                    entity: entity.id().to_string(),
+                    owner: self.auth.owner_of(entity.id()),
+                    step: entity.current_step(),
+                    manifest: entity.manifest().map(manifest_view),
+                };
+
+                self.registry.insert(entity).await;
+
+                CreateEntityResponse::Created(Json(response))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => {
+                CreateEntityResponse::BadRequest(error_body(err.to_string()))
+            }
+            Err(err) => CreateEntityResponse::Failed(error_body(err.to_string())),
+        }
+    }
+
+    #[oai(path = "/life/import", method = "post")]
+    async fn import_life(
+        &self,
+        user: Data<&Arc<User>>,
+        bundle: Binary<Vec<u8>>,
+    ) -> EntityResponse<ImportedEntity> {
+        let entity = {
+            let mut life_manager = self.registry.life_manager().write().await;
+
+            life_manager
+                .import_bundle(bundle.0.as_slice())
+                .map(|entity| entity.id().to_string())
+                .map(|id| life_manager.take_entity(&id).unwrap())
+        };
 
         match entity {
-            Some(entity) => Json(json!({ "entity": entity })),
-            None => Json(json!({ "error": "entity not found" })),
+            Ok(entity) => {
+                if let Err(err) = self.auth.set_owner(entity.id(), &user) {
+                    return EntityResponse::Failed(error_body(err.to_string()));
+                }
+
+                let response = ImportedEntity {
+                    entity: entity.id().to_string(),
+                    step: entity.current_step(),
+                };
+
+                self.registry.insert(entity).await;
+
+                EntityResponse::Ok(Json(response))
+            }
+            Err(err) => EntityResponse::BadRequest(error_body(err.to_string())),
         }
     }
 
+    /// Dumps the whole entity with every unit's memory. Prefer
+    /// `GET /entities/:id` and the step and history endpoints.
+    #[oai(path = "/life/:id", method = "get", deprecated)]
+    async fn get_life(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+    ) -> EntityResponse<EntityDump> {
+        let Some(entity) = self.entity(&user, &id.0).await else {
+            return RegistryError::NotFound.into();
+        };
+
+        let dump = entity_dump(&*entity.lock().await);
+
+        EntityResponse::Ok(Json(dump))
+    }
+
+    /// The entity's step and whether it is idle or computing a step.
+    #[oai(path = "/entity/:id/state", method = "get")]
+    async fn entity_state(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+    ) -> EntityResponse<EntityStateView> {
+        match self.entity(&user, &id.0).await {
+            Some(entity) => EntityResponse::Ok(Json(entity_state_view(&*entity.lock().await))),
+            None => RegistryError::NotFound.into(),
+        }
+    }
+
+    /// Computes the next step and answers once it is saved.
     #[oai(path = "/entity/:id/evolve", method = "post")]
-    async fn evolve_simulation(&self, id: Path<String>) -> Json<Value> {
-        let mut life_manager = self.life_manager.clone();
+    async fn evolve_simulation(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+    ) -> EntityResponse<StepResult> {
+        if !self.auth.can_access(&user, &id.0) {
+            return RegistryError::NotFound.into();
+        }
 
-        let life_manager = Arc::get_mut(&mut life_manager).unwrap();
+        match self.auth.evolve(&self.registry, &user, &id.0).await {
+            Ok(telemetry) => {
+                let step = match self.registry.get(&id.0).await {
+                    Some(entity) => entity.lock().await.current_step(),
+                    None => return RegistryError::NotFound.into(),
+                };
 
-        match life_manager.get_mut_entity(&id.0) {
-            Some(entity) => {
-                let telemetry = entity.evolve_async().await;
-                Json(json!({ "status": "done", "telemetry": telemetry }))
+                EntityResponse::Ok(Json(StepResult {
+                    step,
+                    telemetry: telemetry_view(telemetry),
+                }))
             }
-            None => Json(json!({ "error": "entity not found" })),
+            Err(err) => err.into(),
         }
     }
 
+    /// Edits units between steps. The result is saved as a new step along
+    /// with a record of the intervention.
     #[oai(path = "/entity/:id/interact", method = "post")]
     async fn interact_simulation(
         &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        payload: Json<InteractionPayload>,
+    ) -> EntityResponse<InterventionResult> {
+        let mut entity = match self.lock_idle(&user, &id.0).await {
+            Ok(entity) => entity,
+            Err(err) => return err.into(),
+        };
+
+        let mut intervention = intervention(payload.0, DEFAULT_AUTHOR);
+
+        if self.auth.enabled() {
+            intervention.author = user.name.clone();
+        }
+
+        match entity.intervene(intervention) {
+            Ok(record) => EntityResponse::Ok(Json(InterventionResult {
+                step: entity.current_step(),
+                intervention: intervention_view(record),
+            })),
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => {
+                EntityResponse::BadRequest(error_body(err.to_string()))
+            }
+            Err(err) => EntityResponse::Failed(error_body(err.to_string())),
+        }
+    }
+
+    /// The intervention that produced `step`, or `null` for a computed step.
+    #[oai(path = "/entity/:id/interventions/:step", method = "get")]
+    async fn get_intervention(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        step: Path<u32>,
+    ) -> EntityResponse<InterventionLookup> {
+        match self.entity(&user, &id.0).await {
+            Some(entity) => EntityResponse::Ok(Json(InterventionLookup {
+                intervention: entity
+                    .lock()
+                    .await
+                    .intervention_at(step.0)
+                    .map(intervention_view),
+            })),
+            None => RegistryError::NotFound.into(),
+        }
+    }
+
+    /// Step, state and saved steps of the entity, without its lattice.
+    #[oai(path = "/entities/:id", method = "get")]
+    async fn entity_summary(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+    ) -> EntityResponse<EntitySummary> {
+        match self.entity(&user, &id.0).await {
+            Some(entity) => EntityResponse::Ok(Json(entity_summary(&*entity.lock().await))),
+            None => RegistryError::NotFound.into(),
+        }
+    }
+
+    /// Every unit's state and rule at step `n`.
+    #[oai(path = "/entities/:id/steps/:n", method = "get")]
+    async fn step_grid(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        n: Path<u32>,
+    ) -> EntityResponse<StepGrid> {
+        let Some(entity) = self.entity(&user, &id.0).await else {
+            return RegistryError::NotFound.into();
+        };
+
+        let entity = entity.lock().await;
+
+        match entity.try_load_space_at(n.0) {
+            Ok(space) => {
+                let edited_by = entity
+                    .intervention_at(n.0)
+                    .map(|record| record.intervention.author);
+
+                EntityResponse::Ok(Json(step_grid(
+                    entity.id().to_string(),
+                    n.0,
+                    &space,
+                    edited_by,
+                )))
+            }
+            Err(_) => EntityResponse::NotFound(error_body(format!("step {} not found", n.0))),
+        }
+    }
+
+    /// Step `n` drawn as colored cells; `cell_size` defaults to 16 pixels.
+    /// Frames over 4096×4096 pixels are refused.
+    #[oai(path = "/entities/:id/steps/:n/frame.png", method = "get")]
+    async fn step_frame(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        n: Path<u32>,
+        cell_size: Query<Option<u32>>,
+    ) -> ImageResponse {
+        let cell_size = match parse_cell_size(cell_size.0) {
+            Ok(cell_size) => cell_size,
+            Err(err) => return ImageResponse::BadRequest(error_body(err)),
+        };
+
+        let Some(entity) = self.entity(&user, &id.0).await else {
+            return ImageResponse::NotFound(error_body("entity not found"));
+        };
+
+        let space = match entity.lock().await.try_load_space_at(n.0) {
+            Ok(space) => space,
+            Err(_) => {
+                return ImageResponse::NotFound(error_body(format!("step {} not found", n.0)))
+            }
+        };
+
+        if let Err(err) = check_pixels(frame_pixels(&space, cell_size), 1, MAX_FRAME_PIXELS) {
+            return ImageResponse::BadRequest(error_body(err));
+        }
+
+        match tokio::task::spawn_blocking(move || encode_png(&space, cell_size)).await {
+            Ok(Ok(png)) => ImageResponse::Png(Binary(png)),
+            Ok(Err(err)) => ImageResponse::Failed(error_body(err.to_string())),
+            Err(err) => ImageResponse::Failed(error_body(err.to_string())),
+        }
+    }
+
+    /// Steps `from..=to` (all saved steps by default) as a looping GIF at
+    /// `fps` frames per second (6 by default). At most 600 frames, and 2^28
+    /// pixels over all of them.
+    #[oai(path = "/entities/:id/animation.gif", method = "get")]
+    async fn animation(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        from: Query<Option<u32>>,
+        to: Query<Option<u32>>,
+        fps: Query<Option<u32>>,
+        cell_size: Query<Option<u32>>,
+    ) -> ImageResponse {
+        let cell_size = match parse_cell_size(cell_size.0) {
+            Ok(cell_size) => cell_size,
+            Err(err) => return ImageResponse::BadRequest(error_body(err)),
+        };
+
+        let fps = fps.0.unwrap_or(DEFAULT_FPS).clamp(1, 100);
+
+        let Some(entity) = self.entity(&user, &id.0).await else {
+            return ImageResponse::NotFound(error_body("entity not found"));
+        };
+
+        let snapshots = entity.lock().await.snapshots();
+
+        let spaces = {
+            let steps = snapshots
+                .steps()
+                .iter()
+                .copied()
+                .filter(|step| from.0.is_none_or(|from| *step >= from))
+                .filter(|step| to.0.is_none_or(|to| *step <= to))
+                .collect::<Vec<_>>();
+
+            if steps.is_empty() {
+                return ImageResponse::NotFound(error_body("no steps in range"));
+            }
+
+            if steps.len() > ANIMATION_MAX_FRAMES {
+                return ImageResponse::BadRequest(error_body(format!(
+                    "{} steps in range, at most {ANIMATION_MAX_FRAMES} frames per animation",
+                    steps.len()
+                )));
+            }
+
+            // Every step has the lattice of the first one.
+            if let Ok(first) = snapshots.try_load_space_at(steps[0]) {
+                let pixels = frame_pixels(&first, cell_size);
+
+                if let Err(err) = check_pixels(pixels, 1, MAX_FRAME_PIXELS)
+                    .and_then(|_| check_pixels(pixels, steps.len() as u64, MAX_ANIMATION_PIXELS))
+                {
+                    return ImageResponse::BadRequest(error_body(err));
+                }
+            }
+
+            steps
+                .into_iter()
+                .filter_map(|step| snapshots.try_load_space_at(step).ok())
+                .collect::<Vec<_>>()
+        };
+
+        let started_at = Instant::now();
+        let frames = spaces.len();
+
+        let gif = tokio::task::spawn_blocking(move || {
+            encode_gif(spaces.iter().map(|space| space.as_ref()), cell_size, fps)
+        })
+        .await;
+
+        info!(
+            entity_id = %id.0,
+            frames,
+            fps,
+            cell_size,
+            elapsed_ms = started_at.elapsed().as_millis() as u64,
+            "animation_rendered"
+        );
+
+        match gif {
+            Ok(Ok(gif)) => ImageResponse::Gif(Binary(gif)),
+            Ok(Err(err)) => ImageResponse::Failed(error_body(err.to_string())),
+            Err(err) => ImageResponse::Failed(error_body(err.to_string())),
+        }
+    }
+
+    /// What the unit at `(x, y)` ended every saved step with.
+    #[oai(path = "/entities/:id/units/:x/:y/history", method = "get")]
+    async fn unit_history(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        x: Path<usize>,
+        y: Path<usize>,
+    ) -> EntityResponse<UnitHistory> {
+        let Some(entity) = self.entity(&user, &id.0).await else {
+            return RegistryError::NotFound.into();
+        };
+
+        // Only the step list is read under the lock; parsing every snapshot
+        // happens after it is released.
+        let (entity_id, snapshots) = {
+            let entity = entity.lock().await;
+            (entity.id().to_string(), entity.snapshots())
+        };
+        let position = (x.0, y.0);
+
+        let entries = tokio::task::spawn_blocking(move || {
+            snapshots
+                .unit_history(position)
+                .into_iter()
+                .map(|(step, unit)| {
+                    let edited_by = snapshots
+                        .intervention_at(step)
+                        .filter(|record| record.units_changed.contains(&position))
+                        .map(|record| record.intervention.author);
+
+                    unit_history_entry(step, unit, edited_by)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(err) => return EntityResponse::Failed(error_body(err.to_string())),
+        };
+
+        if entries.is_empty() {
+            return EntityResponse::NotFound(error_body(format!("no unit at {position:?}")));
+        }
+
+        EntityResponse::Ok(Json(UnitHistory {
+            entity_id,
+            x: x.0,
+            y: y.0,
+            entries,
+        }))
+    }
+
+    /// Telemetry of computed steps, oldest first. `limit` defaults to 50 and
+    /// is capped at 500.
+    #[oai(path = "/entities/:id/telemetry", method = "get")]
+    async fn telemetry_history(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        offset: Query<Option<usize>>,
+        limit: Query<Option<usize>>,
+    ) -> EntityResponse<TelemetryPage> {
+        let Some(entity) = self.entity(&user, &id.0).await else {
+            return RegistryError::NotFound.into();
+        };
+
+        let offset = offset.0.unwrap_or(0);
+        let limit = limit
+            .0
+            .unwrap_or(TELEMETRY_PAGE_SIZE)
+            .min(TELEMETRY_MAX_PAGE_SIZE);
+
+        let entity = entity.lock().await;
+        let (total, items) = entity.telemetry_history(offset, limit);
+
+        EntityResponse::Ok(Json(TelemetryPage {
+            entity_id: entity.id().to_string(),
+            total,
+            offset,
+            limit,
+            items: items
+                .into_iter()
+                .map(|(step, telemetry)| StepTelemetryEntry {
+                    step,
+                    telemetry: telemetry_view(telemetry),
+                })
+                .collect(),
+        }))
+    }
+
+    /// Server-sent events for every step of the entity: `step_started`, one
+    /// `unit_completed` per unit as it arrives, then `step_completed` with the
+    /// step telemetry.
+    #[oai(path = "/entities/:id/events", method = "get")]
+    async fn entity_events(&self, user: Data<&Arc<User>>, id: Path<String>) -> EventsResponse {
+        let receiver = match self.auth.can_access(&user, &id.0) {
+            true => self.registry.subscribe(&id.0).await,
+            false => None,
+        };
+
+        let Some(receiver) = receiver else {
+            return EventsResponse::NotFound(error_body("entity not found"));
+        };
+
+        let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(event) => Some((step_event_view(event), receiver)),
+                Err(RecvError::Lagged(skipped)) => {
+                    Some((StepEventView::Lagged(LaggedEvent { skipped }), receiver))
+                }
+                Err(RecvError::Closed) => None,
+            }
+        });
+
+        EventsResponse::Stream(
+            EventStream::new(stream.boxed())
+                .keep_alive(EVENTS_KEEP_ALIVE)
+                .to_event(|event| Event::message(event.to_json_string()).event_type(event.kind())),
+        )
+    }
+
+    /// Starts evolving the entity in the background; poll the returned run.
+    /// Its steps count against the caller's quota.
+    #[oai(path = "/entities/:id/runs", method = "post")]
+    async fn start_run(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        payload: Json<RunPayload>,
+    ) -> EntityResponse<RunView> {
+        if !self.auth.can_access(&user, &id.0) {
+            return RegistryError::NotFound.into();
+        }
+
+        match self
+            .runs
+            .start(&id.0, run_spec(payload.0), user.0.clone())
+            .await
+        {
+            Ok(run) => EntityResponse::Accepted(Json(run_view(run))),
+            Err(err) => err.into(),
+        }
+    }
+
+    #[oai(path = "/entities/:id/runs", method = "get")]
+    async fn list_runs(&self, user: Data<&Arc<User>>, id: Path<String>) -> Json<RunList> {
+        if !self.auth.can_access(&user, &id.0) {
+            return Json(RunList { runs: vec![] });
+        }
+
+        Json(RunList {
+            runs: self
+                .runs
+                .list(&id.0)
+                .await
+                .into_iter()
+                .map(run_view)
+                .collect(),
+        })
+    }
+
+    #[oai(path = "/runs/:run", method = "get")]
+    async fn get_run(&self, user: Data<&Arc<User>>, run: Path<String>) -> EntityResponse<RunView> {
+        match self.run(&user, &run.0).await {
+            Some(run) => EntityResponse::Ok(Json(run_view(run))),
+            None => RunError::NotFound.into(),
+        }
+    }
+
+    #[oai(path = "/runs/:run/pause", method = "post")]
+    async fn pause_run(
+        &self,
+        user: Data<&Arc<User>>,
+        run: Path<String>,
+    ) -> EntityResponse<RunView> {
+        if self.run(&user, &run.0).await.is_none() {
+            return RunError::NotFound.into();
+        }
+
+        match self.runs.pause(&run.0).await {
+            Ok(run) => EntityResponse::Ok(Json(run_view(run))),
+            Err(err) => err.into(),
+        }
+    }
+
+    #[oai(path = "/runs/:run/resume", method = "post")]
+    async fn resume_run(
+        &self,
+        user: Data<&Arc<User>>,
+        run: Path<String>,
+    ) -> EntityResponse<RunView> {
+        if self.run(&user, &run.0).await.is_none() {
+            return RunError::NotFound.into();
+        }
+
+        match self.runs.resume(&run.0).await {
+            Ok(run) => EntityResponse::Ok(Json(run_view(run))),
+            Err(err) => err.into(),
+        }
+    }
+
+    #[oai(path = "/runs/:run/cancel", method = "post")]
+    async fn cancel_run(
+        &self,
+        user: Data<&Arc<User>>,
+        run: Path<String>,
+    ) -> EntityResponse<RunView> {
+        if self.run(&user, &run.0).await.is_none() {
+            return RunError::NotFound.into();
+        }
+
+        match self.runs.cancel(&run.0).await {
+            Ok(run) => EntityResponse::Ok(Json(run_view(run))),
+            Err(err) => err.into(),
+        }
+    }
+
+    #[oai(path = "/entity/:id/rewind", method = "post")]
+    async fn rewind_simulation(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        payload: Json<RewindPayload>,
+    ) -> EntityResponse<RewindResult> {
+        let mut entity = match self.lock_idle(&user, &id.0).await {
+            Ok(entity) => entity,
+            Err(err) => return err.into(),
+        };
+
+        match entity.rewind_to(payload.0.step) {
+            Ok(archive) => {
+                let archive = archive
+                    .and_then(|archive| archive.file_name().map(|name| name.to_owned()))
+                    .map(|name| name.to_string_lossy().to_string());
+
+                EntityResponse::Ok(Json(RewindResult {
+                    step: entity.current_step(),
+                    archive,
+                }))
+            }
+            Err(err) => err.into(),
+        }
+    }
+
+    #[oai(path = "/entity/:id/export", method = "get")]
+    async fn export_simulation(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        frames: Query<Option<bool>>,
+    ) -> ExportResponse {
+        let Some(entity) = self.entity(&user, &id.0).await else {
+            return ExportResponse::NotFound(error_body("entity not found"));
+        };
+
+        let entity = entity.lock().await;
+        let mut bundle = vec![];
+
+        match export_entity(&entity, &mut bundle, frames.0.unwrap_or(false)) {
+            Ok(_) => ExportResponse::Bundle(
+                Attachment::new(bundle)
+                    .attachment_type(AttachmentType::Attachment)
+                    .filename(format!("{}.{}", entity.id(), BUNDLE_EXTENSION)),
+            ),
+            Err(err) => ExportResponse::Failed(error_body(err.to_string())),
+        }
+    }
+
+    #[oai(path = "/entity/:id/archives", method = "get")]
+    async fn list_archives(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+    ) -> EntityResponse<ArchiveList> {
+        match self.entity(&user, &id.0).await {
+            Some(entity) => EntityResponse::Ok(Json(ArchiveList {
+                archives: entity.lock().await.list_archives(),
+            })),
+            None => RegistryError::NotFound.into(),
+        }
+    }
+
+    #[oai(path = "/entity/:id/archives/:archive/restore", method = "post")]
+    async fn restore_archive(
+        &self,
+        user: Data<&Arc<User>>,
+        id: Path<String>,
+        archive: Path<String>,
+    ) -> EntityResponse<RestoredArchive> {
+        let mut entity = match self.lock_idle(&user, &id.0).await {
+            Ok(entity) => entity,
+            Err(err) => return err.into(),
+        };
+
+        match entity.restore_archive(&archive.0) {
+            Ok(()) => EntityResponse::Ok(Json(RestoredArchive {
+                step: entity.current_step(),
+            })),
+            Err(err) => err.into(),
+        }
+    }
+
+    /// Every configured resolver, including disabled ones.
+    #[oai(path = "/resolvers", method = "get")]
+    async fn list_resolvers(&self) -> ResolverListResponse {
+        let resolvers = self.registry.life_manager().read().await.all_resolvers();
+
+        ResolverListResponse::Ok(Json(resolvers.iter().map(resolver_view).collect()))
+    }
+
+    /// Adds a resolver and saves it to the resolvers file. Admins only.
+    #[oai(path = "/resolvers", method = "post")]
+    async fn add_resolver(
+        &self,
+        user: Data<&Arc<User>>,
+        payload: Json<ResolverPayload>,
+    ) -> ResolverResponse {
+        let resolver = llm_resolver(payload.0);
+
+        self.edit_resolvers(&user, |resolvers| {
+            if resolver.id().trim().is_empty() || resolver.model_name().trim().is_empty() {
+                return ResolverResponse::BadRequest(error_body(
+                    "name and model_name must not be empty",
+                ));
+            }
+
+            if !resolver.api_url().starts_with("http://")
+                && !resolver.api_url().starts_with("https://")
+            {
+                return ResolverResponse::BadRequest(error_body(
+                    "api_url must be an http or https URL",
+                ));
+            }
+
+            if resolvers
+                .iter()
+                .any(|existing| existing.id() == resolver.id())
+            {
+                return ResolverResponse::Conflict(error_body(format!(
+                    "resolver {} already exists",
+                    resolver.id()
+                )));
+            }
+
+            let view = resolver_view(&resolver);
+            resolvers.push(resolver);
+
+            ResolverResponse::Created(Json(view))
+        })
+        .await
+    }
+
+    /// Removes a resolver from the resolvers file. Admins only.
+    #[oai(path = "/resolvers/:id", method = "delete")]
+    async fn remove_resolver(&self, user: Data<&Arc<User>>, id: Path<String>) -> ResolverResponse {
+        self.edit_resolvers(&user, |resolvers| {
+            let Some(index) = resolver_index(resolvers, &id.0) else {
+                return ResolverResponse::NotFound(error_body("resolver not found"));
+            };
+
+            let removed = resolvers.remove(index);
+
+            ResolverResponse::Ok(Json(resolver_view(&removed)))
+        })
+        .await
+    }
+
+    /// Puts a disabled resolver back in the pool. Admins only.
+    #[oai(path = "/resolvers/:id/enable", method = "post")]
+    async fn enable_resolver(&self, user: Data<&Arc<User>>, id: Path<String>) -> ResolverResponse {
+        self.set_resolver_enabled(&user, &id.0, true).await
+    }
+
+    /// Keeps a resolver configured but stops sending it requests from the
+    /// next step on. Admins only.
+    #[oai(path = "/resolvers/:id/disable", method = "post")]
+    async fn disable_resolver(&self, user: Data<&Arc<User>>, id: Path<String>) -> ResolverResponse {
+        self.set_resolver_enabled(&user, &id.0, false).await
+    }
+
+    /// Re-reads the resolvers file now instead of waiting for the watcher.
+    /// Admins only.
+    #[oai(path = "/resolvers/reload", method = "post")]
+    async fn reload_resolvers(&self, user: Data<&Arc<User>>) -> ResolverListResponse {
+        if !user.admin {
+            return ResolverListResponse::Forbidden(error_body("admins only"));
+        }
+
+        let life_manager = self.registry.life_manager().read().await;
+
+        match life_manager.reload_resolvers() {
+            Ok(_) => ResolverListResponse::Ok(Json(
+                life_manager
+                    .all_resolvers()
+                    .iter()
+                    .map(resolver_view)
+                    .collect(),
+            )),
+            Err(err) => ResolverListResponse::Failed(error_body(err.to_string())),
+        }
+    }
+
+    /// Asks every resolver's API for its model list, in parallel. Admins
+    /// only, since every call reaches out to the providers.
+    #[oai(path = "/resolvers/health", method = "get")]
+    async fn resolvers_health(&self, user: Data<&Arc<User>>) -> ResolverHealthResponse {
+        if !user.admin {
+            return ResolverHealthResponse::Forbidden(error_body("admins only"));
+        }
+
+        let resolvers = self.registry.life_manager().read().await.all_resolvers();
+        let health = futures::future::join_all(resolvers.iter().map(check_resolver)).await;
+
+        ResolverHealthResponse::Ok(Json(health.into_iter().map(resolver_health_view).collect()))
+    }
+
+    /// Asks one resolver's API for its model list. Admins only.
+    #[oai(path = "/resolvers/:id/health", method = "get")]
+    async fn resolver_health(
+        &self,
+        user: Data<&Arc<User>>,
         id: Path<String>,
-        payload: Json<Vec<String>>,
-    ) -> Json<Value> {
-        let mut life_manager = self.life_manager.clone();
+    ) -> ResolverHealthResponse {
+        if !user.admin {
+            return ResolverHealthResponse::Forbidden(error_body("admins only"));
+        }
+
+        let resolver = self
+            .registry
+            .life_manager()
+            .read()
+            .await
+            .all_resolvers()
+            .into_iter()
+            .find(|resolver| resolver.id() == id.0);
+
+        match resolver {
+            Some(resolver) => ResolverHealthResponse::Ok(Json(vec![resolver_health_view(
+                check_resolver(&resolver).await,
+            )])),
+            None => ResolverHealthResponse::NotFound(error_body("resolver not found")),
+        }
+    }
+}
 
-        let life_manager = Arc::get_mut(&mut life_manager).unwrap();
+impl Api {
+    /// The entity, if it exists and `user` may access it. Entities of other
+    /// users look like missing ones.
+    async fn entity(&self, user: &User, id: &str) -> Option<SharedEntity> {
+        if !self.auth.can_access(user, id) {
+            return None;
+        }
 
-        let entity = life_manager.get_mut_entity(&id.0).unwrap();
+        self.registry.get(id).await
+    }
 
-        // entity.space().set_unit(i, j, unit);
+    async fn lock_idle(
+        &self,
+        user: &User,
+        id: &str,
+    ) -> Result<OwnedMutexGuard<Entity>, RegistryError> {
+        if !self.auth.can_access(user, id) {
+            return Err(RegistryError::NotFound);
+        }
 
-        println!("Payload: {:?}", payload);
-        println!("Entity: {:?}", entity);
+        self.registry.lock_idle(id).await
+    }
 
-        todo!()
+    /// Applies `edit` to the configured resolvers and saves the result,
+    /// holding the manager lock so concurrent edits don't overwrite each
+    /// other. Nothing is saved unless `edit` answers with a success.
+    async fn edit_resolvers(
+        &self,
+        user: &User,
+        edit: impl FnOnce(&mut Vec<LLMResolver>) -> ResolverResponse,
+    ) -> ResolverResponse {
+        if !user.admin {
+            return ResolverResponse::Forbidden(error_body("admins only"));
+        }
 
-        // match a.get_mut_entity(&id.0) {
-        //     Some(entity) => {
-        //         entity.interact(payload.0).await;
-        //         Json(json!({ "status": "done" }))
-        //     }
-        //     None => Json(json!({ "error": "entity not found" })),
-        // }
+        let life_manager = self.registry.life_manager().write().await;
+        let mut resolvers = life_manager.all_resolvers();
+
+        let response = edit(&mut resolvers);
+
+        if !matches!(
+            response,
+            ResolverResponse::Ok(_) | ResolverResponse::Created(_)
+        ) {
+            return response;
+        }
+
+        match life_manager.save_resolvers(resolvers) {
+            Ok(()) => {
+                info!(user = %user.name, "resolvers_edited");
+                response
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => {
+                ResolverResponse::BadRequest(error_body(err.to_string()))
+            }
+            Err(err) => ResolverResponse::Failed(error_body(err.to_string())),
+        }
+    }
+
+    async fn set_resolver_enabled(&self, user: &User, id: &str, enabled: bool) -> ResolverResponse {
+        self.edit_resolvers(user, |resolvers| {
+            let Some(index) = resolver_index(resolvers, id) else {
+                return ResolverResponse::NotFound(error_body("resolver not found"));
+            };
+
+            resolvers[index].set_enabled(enabled);
+
+            ResolverResponse::Ok(Json(resolver_view(&resolvers[index])))
+        })
+        .await
+    }
+
+    async fn run(&self, user: &User, id: &str) -> Option<RunStatus> {
+        self.runs
+            .get(id)
+            .await
+            .filter(|run| self.auth.can_access(user, &run.entity_id))
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
-    init_tracing();
+    dotenv().ok();
+    let _tracing = init_tracing("llmca-server", LogFormat::Json);
+
+    let (config, args) = ServerConfig::from_env_and_args()?;
+    let metrics = metrics::install_recorder().map_err(std::io::Error::other)?;
+    let life_manager = LifeManager::with_config(LifeManagerConfig::from_env().with_args(args)?)?;
+
+    let auth = Arc::new(Auth::new(config.users, life_manager.root_folder()));
+    let registry = Arc::new(EntityRegistry::new(life_manager));
+    watch_resolvers(registry.clone());
+    let runs = Arc::new(RunManager::new(registry.clone(), auth.clone()));
 
     let api_service = OpenApiService::new(
         Api {
-            life_manager: Arc::new(LifeManager::default()),
+            registry,
+            runs,
+            auth: auth.clone(),
         },
         "LLMCA API",
         "1.0",
     )
     .server("http://localhost:8000/api");
 
-    // let ui = api_service.swagger_ui();
-    let app = Route::new().nest("/api", api_service).with(
-        Cors::new()
-            .allow_origin("*")
-            .allow_methods(vec!["GET", "POST", "OPTIONS"])
-            .allow_headers(vec!["Content-Type", "Authorization"]),
-    );
+    let cors = config
+        .allowed_origins
+        .iter()
+        .fold(Cors::new(), |cors, origin| cors.allow_origin_regex(origin))
+        .allow_methods(vec!["GET", "POST", "DELETE", "OPTIONS"])
+        .allow_headers(vec!["Content-Type", "Authorization", API_KEY_HEADER]);
 
-    info!(addr = "0.0.0.0:8000", "server_starting");
+    // The spec and the UI are public; the API needs a key and the metrics an
+    // admin's, since they name every entity and resolver.
+    let docs = api_service.swagger_ui();
+    let spec = api_service.spec_endpoint();
+    let metrics = make_sync(move |_| {
+        Response::builder()
+            .content_type("text/plain; version=0.0.4")
+            .body(metrics.render())
+    });
 
-    poem::Server::new(TcpListener::bind("0.0.0.0:8000"))
+    let metrics_auth = auth.clone();
+
+    let app = Route::new()
+        .nest(
+            "/api",
+            api_service.around(move |endpoint, req| require_user(auth.clone(), endpoint, req)),
+        )
+        .nest("/docs", docs)
+        .at("/openapi.json", spec)
+        .at(
+            "/metrics",
+            metrics.around(move |endpoint, req| require_admin(metrics_auth.clone(), endpoint, req)),
+        )
+        .with(cors);
+
+    info!(addr = %config.bind, docs = "/docs", "server_starting");
+
+    poem::Server::new(TcpListener::bind(config.bind))
         .run(app)
         .await
 }
 
-fn init_tracing() {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+fn resolver_index(resolvers: &[LLMResolver], id: &str) -> Option<usize> {
+    resolvers.iter().position(|resolver| resolver.id() == id)
+}
+
+/// Checks `frames` frames of `pixels` each against `limit`.
+fn check_pixels(pixels: Option<u64>, frames: u64, limit: u64) -> Result<(), String> {
+    match pixels.and_then(|pixels| pixels.checked_mul(frames)) {
+        Some(total) if total <= limit => Ok(()),
+        _ => Err(format!(
+            "{frames} frame(s) at this cell_size exceed {limit} pixels; use a smaller cell_size or fewer steps"
+        )),
+    }
+}
 
-    let _ = tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(true)
-        .json()
-        .try_init();
+fn parse_cell_size(cell_size: Option<u32>) -> Result<u32, String> {
+    match cell_size.unwrap_or(DEFAULT_CELL_SIZE) {
+        cell_size @ 1..=MAX_CELL_SIZE => Ok(cell_size),
+        cell_size => Err(format!(
+            "cell_size must be between 1 and {MAX_CELL_SIZE}, got {cell_size}"
+        )),
+    }
 }
@@ -0,0 +1,793 @@
+//! Response enums of the API and conversions between the library types and
+//! the request and response bodies defined in the `client` crate.
+
+use std::{collections::BTreeMap, io};
+
+pub use client::*;
+use dynamical_system::{
+    life::{
+        entity::{Entity, EntityState},
+        events::StepEvent,
+        intervention::{Intervention, InterventionAction as Action, InterventionRecord},
+        manifest::{EntityManifest, EntitySpec, SpeciesLayout, SpeciesSpec},
+        run::{self, RunSpec, RunStatus, StopConditions},
+        usage::EntityUsage,
+    },
+    system::{
+        assignment::AssignmentStrategy,
+        health::ResolverHealth,
+        policy::{CircuitBreakerPolicy, ResolverPolicy},
+        space::{
+            CognitiveSpaceWithMemory, LLMResolver, LatticeTopology, Neighborhood, ResolverPricing,
+        },
+        telemetry::{ResolverStats, ResolverUsage, StepTelemetry},
+        unit::LLMProvider,
+        unit_next::{CognitiveUnitComplex, CognitiveUnitPair, UnitOutcome},
+    },
+};
+use futures::stream::BoxStream;
+use poem_openapi::{
+    payload::{Attachment, Binary, EventStream, Json},
+    types::ToJSON,
+    ApiResponse,
+};
+
+use crate::{registry::RegistryError, runs::RunError};
+
+pub fn error_body(error: impl Into<String>) -> Json<ErrorBody> {
+    Json(ErrorBody::new(error))
+}
+
+pub fn telemetry_view(telemetry: StepTelemetry) -> TelemetryView {
+    TelemetryView {
+        units_total: telemetry.units_total,
+        units_completed: telemetry.units_completed,
+        resolver_count: telemetry.resolver_count,
+        chunks: telemetry.chunks,
+        llm_failures: telemetry.llm_failures,
+        parse_failures: telemetry.parse_failures,
+        outcomes: telemetry
+            .outcomes
+            .iter()
+            .map(|(outcome, count)| (outcome_key(*outcome), *count))
+            .collect(),
+        failovers: telemetry.failovers,
+        hedged: telemetry.hedged,
+        hedge_wins: telemetry.hedge_wins,
+        species: telemetry.species,
+        species_adoptions: telemetry.species_adoptions,
+        cache_hits: telemetry.cache_hits,
+        cache_misses: telemetry.cache_misses,
+        cache_hit_rate: telemetry.cache_hit_rate,
+        unique_states: telemetry.unique_states,
+        total_tokens: telemetry.total_tokens,
+        input_tokens: telemetry.input_tokens,
+        output_tokens: telemetry.output_tokens,
+        cost: telemetry.cost,
+        resolvers: resolver_usage_views(&telemetry.resolvers),
+        resolver_stats: telemetry
+            .resolver_stats
+            .iter()
+            .map(|(id, stats)| (id.clone(), resolver_stats_view(stats)))
+            .collect(),
+        elapsed_ms: telemetry.elapsed_ms,
+    }
+}
+
+fn resolver_usage_views(
+    resolvers: &BTreeMap<String, ResolverUsage>,
+) -> BTreeMap<String, ResolverUsageView> {
+    resolvers
+        .iter()
+        .map(|(id, usage)| {
+            let view = ResolverUsageView {
+                units: usage.units,
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+                total_tokens: usage.total_tokens,
+                cost: usage.cost,
+            };
+
+            (id.clone(), view)
+        })
+        .collect()
+}
+
+pub fn unit_outcome_view(outcome: UnitOutcome) -> UnitOutcomeView {
+    match outcome {
+        UnitOutcome::Ok => UnitOutcomeView::Ok,
+        UnitOutcome::RetriedOk => UnitOutcomeView::RetriedOk,
+        UnitOutcome::TransportError => UnitOutcomeView::TransportError,
+        UnitOutcome::Timeout => UnitOutcomeView::Timeout,
+        UnitOutcome::SchemaViolation => UnitOutcomeView::SchemaViolation,
+        UnitOutcome::ValidationRejected => UnitOutcomeView::ValidationRejected,
+    }
+}
+
+/// `outcome` as its snake_case name, the way [`UnitOutcomeView`] spells it.
+fn outcome_key(outcome: UnitOutcome) -> String {
+    serde_json::to_value(outcome)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn resolver_stats_view(stats: &ResolverStats) -> ResolverStatsView {
+    ResolverStatsView {
+        calls: stats.calls,
+        successes: stats.successes,
+        retried_ok: stats.retried_ok,
+        transport_failures: stats.transport_failures,
+        timeouts: stats.timeouts,
+        structured_output_failures: stats.structured_output_failures,
+        validation_rejections: stats.validation_rejections,
+        success_rate: stats.success_rate,
+        latency_p50_ms: stats.latency_p50_ms,
+        latency_p95_ms: stats.latency_p95_ms,
+        latency_p99_ms: stats.latency_p99_ms,
+        latency_max_ms: stats.latency_max_ms,
+    }
+}
+
+pub fn entity_usage_view(usage: &EntityUsage) -> EntityUsageView {
+    EntityUsageView {
+        steps: usage.steps,
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+        total_tokens: usage.total_tokens,
+        cost: usage.cost,
+        resolvers: resolver_usage_views(&usage.resolvers),
+    }
+}
+
+fn activity(entity: &Entity) -> (EntityActivity, Option<u32>) {
+    match entity.state() {
+        EntityState::Idle => (EntityActivity::Idle, None),
+        EntityState::ComputingStep(step) => (EntityActivity::Computing, Some(*step)),
+    }
+}
+
+pub fn entity_state_view(entity: &Entity) -> EntityStateView {
+    let (state, computing_step) = activity(entity);
+
+    EntityStateView {
+        step: entity.current_step(),
+        state,
+        computing_step,
+    }
+}
+
+pub fn entity_summary(entity: &Entity) -> EntitySummary {
+    let (state, computing_step) = activity(entity);
+
+    EntitySummary {
+        id: entity.id().to_string(),
+        step: entity.current_step(),
+        state,
+        computing_step,
+        saved_steps: entity.saved_steps(),
+        usage: entity_usage_view(entity.usage()),
+    }
+}
+
+pub fn entity_dump(entity: &Entity) -> EntityDump {
+    let mut units = entity.loaded_space().get_units();
+    units.sort_by_key(|unit| unit.position);
+
+    EntityDump {
+        entity: entity_summary(entity),
+        manifest: entity.manifest().map(manifest_view),
+        units: units
+            .into_iter()
+            .map(|unit| UnitDump {
+                x: unit.position.0,
+                y: unit.position.1,
+                species: unit.species,
+                memory_size: unit.memory_size,
+                memory: unit
+                    .memory
+                    .into_iter()
+                    .map(|entry| MemoryEntryView {
+                        timestamp: entry.timestamp.to_rfc3339(),
+                        rule: entry.rule,
+                        state: entry.state,
+                        feedback: entry.feedback,
+                        outcome: unit_outcome_view(entry.outcome),
+                        tokens: entry.tokens,
+                        resolver_id: entry.resolver_id,
+                        cached: entry.cached,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+pub fn manifest_view(manifest: &EntityManifest) -> ManifestView {
+    let spec = &manifest.spec;
+
+    ManifestView {
+        id: manifest.id.clone(),
+        created_at: manifest.created_at.to_rfc3339(),
+        width: spec.size.0,
+        height: spec.size.1,
+        memory_size: spec.memory_size,
+        initial_states: spec
+            .initial_states
+            .iter()
+            .map(|pair| InitialStatePayload {
+                rule: pair.rule.clone(),
+                state: pair.state.clone(),
+            })
+            .collect(),
+        topology: TopologyPayload {
+            neighborhood: Some(match spec.topology.neighborhood {
+                Neighborhood::Moore => NeighborhoodPayload::Moore,
+                Neighborhood::VonNeumann => NeighborhoodPayload::VonNeumann,
+            }),
+            wrap: Some(spec.topology.wrap),
+        },
+        resolvers: spec.resolvers.clone(),
+        assignment: assignment_payload(spec.assignment),
+        species: spec.species.as_ref().map(|species| SpeciesPayload {
+            resolvers: species.resolvers.clone(),
+            layout: Some(match species.layout {
+                SpeciesLayout::Bands => SpeciesLayoutPayload::Bands,
+                SpeciesLayout::Cyclic => SpeciesLayoutPayload::Cyclic,
+            }),
+            inherit: Some(species.inherit),
+        }),
+        cache_responses: spec.cache_responses,
+    }
+}
+
+pub fn step_grid(
+    entity_id: String,
+    step: u32,
+    space: &CognitiveSpaceWithMemory,
+    edited_by: Option<String>,
+) -> StepGrid {
+    let units = space.get_units();
+
+    let width = units.iter().map(|u| u.position.0 + 1).max().unwrap_or(0);
+    let height = units.iter().map(|u| u.position.1 + 1).max().unwrap_or(0);
+
+    let mut states = vec![vec![String::new(); height]; width];
+    let mut rules = vec![vec![String::new(); height]; width];
+    let mut species = vec![vec![String::new(); height]; width];
+
+    for unit in &units {
+        if let Some(last) = unit.memory.last() {
+            let (x, y) = unit.position;
+
+            states[x][y] = last.state.clone();
+            rules[x][y] = last.rule.clone();
+        }
+
+        if let Some(unit_species) = &unit.species {
+            species[unit.position.0][unit.position.1] = unit_species.clone();
+        }
+    }
+
+    StepGrid {
+        entity_id,
+        step,
+        width,
+        height,
+        states,
+        rules,
+        species,
+        edited_by,
+    }
+}
+
+pub fn unit_history_entry(
+    step: u32,
+    unit: CognitiveUnitComplex,
+    edited_by: Option<String>,
+) -> UnitHistoryEntry {
+    UnitHistoryEntry {
+        step,
+        timestamp: unit.timestamp.to_rfc3339(),
+        rule: unit.rule,
+        state: unit.state,
+        feedback: unit.feedback,
+        outcome: unit_outcome_view(unit.outcome),
+        tokens: unit.tokens,
+        resolver_id: unit.resolver_id,
+        attempts: unit
+            .attempts
+            .into_iter()
+            .map(|attempt| ResolverAttemptView {
+                resolver_id: attempt.resolver_id,
+                outcome: unit_outcome_view(attempt.outcome),
+                latency_ms: attempt.latency_ms,
+                input_tokens: attempt.input_tokens,
+                output_tokens: attempt.output_tokens,
+                feedback: attempt.feedback,
+            })
+            .collect(),
+        hedged_to: unit.hedged_to,
+        cached: unit.cached,
+        edited_by,
+    }
+}
+
+pub fn intervention_view(record: InterventionRecord) -> InterventionView {
+    InterventionView {
+        step: record.step,
+        applied_at: record.applied_at.to_rfc3339(),
+        author: record.intervention.author,
+        note: record.intervention.note,
+        actions: record
+            .intervention
+            .actions
+            .into_iter()
+            .map(|action| match action {
+                Action::SetUnit {
+                    position: (x, y),
+                    rule,
+                    state,
+                } => InteractionAction::SetUnit(SetUnitAction { x, y, rule, state }),
+                Action::Feedback {
+                    position: (x, y),
+                    message,
+                } => InteractionAction::Feedback(FeedbackAction { x, y, message }),
+                Action::Paint {
+                    from,
+                    to,
+                    rule,
+                    state,
+                } => InteractionAction::Paint(PaintAction {
+                    from_x: from.0,
+                    from_y: from.1,
+                    to_x: to.0,
+                    to_y: to.1,
+                    rule,
+                    state,
+                }),
+                Action::Broadcast { message } => {
+                    InteractionAction::Broadcast(BroadcastAction { message })
+                }
+            })
+            .collect(),
+        units_changed: record
+            .units_changed
+            .into_iter()
+            .map(|(x, y)| Position { x, y })
+            .collect(),
+    }
+}
+
+pub fn run_view(run: RunStatus) -> RunView {
+    RunView {
+        id: run.id,
+        entity_id: run.entity_id,
+        spec: RunPayload {
+            steps: run.spec.max_steps,
+            stop_on_fixed_point: Some(run.spec.stop_when.fixed_point),
+            unique_states_below: run.spec.stop_when.unique_states_below,
+            token_budget: run.spec.stop_when.token_budget,
+            cost_budget: run.spec.stop_when.cost_budget,
+            max_duration_secs: run.spec.stop_when.max_duration_secs,
+        },
+        state: match run.state {
+            run::RunState::Running => RunState::Running,
+            run::RunState::Paused => RunState::Paused,
+            run::RunState::Finished => RunState::Finished,
+            run::RunState::Failed => RunState::Failed,
+        },
+        stop_reason: run.stop_reason.map(|reason| match reason {
+            run::StopReason::StepsCompleted => StopReason::StepsCompleted,
+            run::StopReason::FixedPoint => StopReason::FixedPoint,
+            run::StopReason::UniqueStatesBelow => StopReason::UniqueStatesBelow,
+            run::StopReason::TokenBudgetExceeded => StopReason::TokenBudgetExceeded,
+            run::StopReason::CostBudgetExceeded => StopReason::CostBudgetExceeded,
+            run::StopReason::TimeLimit => StopReason::TimeLimit,
+            run::StopReason::QuotaExceeded => StopReason::QuotaExceeded,
+            run::StopReason::Cancelled => StopReason::Cancelled,
+        }),
+        error: run.error,
+        start_step: run.start_step,
+        steps_completed: run.steps_completed,
+        tokens_used: run.tokens_used,
+        cost_used: run.cost_used,
+        elapsed_ms: run.elapsed_ms,
+        last_telemetry: run.last_telemetry.map(telemetry_view),
+        started_at: run.started_at.to_rfc3339(),
+        finished_at: run.finished_at.map(|at| at.to_rfc3339()),
+    }
+}
+
+pub fn step_event_view(event: StepEvent) -> StepEventView {
+    match event {
+        StepEvent::StepStarted {
+            entity_id,
+            step,
+            units_total,
+        } => StepEventView::StepStarted(StepStartedEvent {
+            entity_id,
+            step,
+            units_total,
+        }),
+        StepEvent::UnitCompleted {
+            entity_id,
+            step,
+            position: (x, y),
+            rule,
+            state,
+            feedback,
+            outcome,
+        } => StepEventView::UnitCompleted(UnitCompletedEvent {
+            entity_id,
+            step,
+            x,
+            y,
+            rule,
+            state,
+            feedback,
+            outcome: unit_outcome_view(outcome),
+        }),
+        StepEvent::StepCompleted {
+            entity_id,
+            step,
+            telemetry,
+        } => StepEventView::StepCompleted(StepCompletedEvent {
+            entity_id,
+            step,
+            telemetry: telemetry_view(telemetry),
+        }),
+    }
+}
+
+pub fn resolver_view(resolver: &LLMResolver) -> ResolverView {
+    let api_key = resolver.api_key().trim();
+
+    ResolverView {
+        id: resolver.id().to_string(),
+        name: resolver.name().map(str::to_string),
+        provider: match resolver.provider() {
+            LLMProvider::Ollama => ProviderPayload::Ollama,
+            LLMProvider::OpenRouter => ProviderPayload::OpenRouter,
+        },
+        api_url: resolver.api_url().to_string(),
+        model_name: resolver.model_name().to_string(),
+        api_key_set: !api_key.is_empty() && api_key != "_",
+        enabled: resolver.is_enabled(),
+        pricing: resolver.pricing().map(|pricing| PricingPayload {
+            input_per_million: pricing.input_per_million,
+            output_per_million: pricing.output_per_million,
+        }),
+        capacity: resolver.capacity(),
+        policy: policy_payload(resolver.policy()),
+        circuit_open: !resolver.guard().is_available(),
+    }
+}
+
+fn policy_payload(policy: &ResolverPolicy) -> PolicyPayload {
+    PolicyPayload {
+        timeout_secs: policy.timeout_secs,
+        retries: Some(policy.retries),
+        backoff_ms: Some(policy.backoff_ms),
+        requests_per_minute: policy.requests_per_minute,
+        tokens_per_minute: policy.tokens_per_minute,
+        circuit_breaker: policy.circuit_breaker.map(|breaker| CircuitBreakerPayload {
+            failures: breaker.failures,
+            cooldown_secs: Some(breaker.cooldown_secs),
+        }),
+        failover: Some(policy.failover.clone()),
+        hedge_after_ms: policy.hedge_after_ms,
+    }
+}
+
+fn resolver_policy(payload: PolicyPayload) -> ResolverPolicy {
+    let defaults = ResolverPolicy::default();
+
+    ResolverPolicy {
+        timeout_secs: payload.timeout_secs,
+        retries: payload.retries.unwrap_or(defaults.retries),
+        backoff_ms: payload.backoff_ms.unwrap_or(defaults.backoff_ms),
+        requests_per_minute: payload.requests_per_minute,
+        tokens_per_minute: payload.tokens_per_minute,
+        circuit_breaker: payload.circuit_breaker.map(|breaker| CircuitBreakerPolicy {
+            failures: breaker.failures,
+            cooldown_secs: breaker
+                .cooldown_secs
+                .unwrap_or(CircuitBreakerPolicy::DEFAULT_COOLDOWN_SECS),
+        }),
+        failover: payload.failover.unwrap_or_default(),
+        hedge_after_ms: payload.hedge_after_ms,
+    }
+}
+
+pub fn resolver_health_view(health: ResolverHealth) -> ResolverHealthView {
+    ResolverHealthView {
+        resolver_id: health.resolver_id,
+        healthy: health.healthy,
+        status: health.status,
+        model_available: health.model_available,
+        error: health.error,
+        latency_ms: health.latency_ms,
+        checked_at: health.checked_at.to_rfc3339(),
+    }
+}
+
+pub fn entity_spec(payload: CreateEntityPayload) -> EntitySpec {
+    let topology = payload.topology.map_or_else(LatticeTopology::default, |t| {
+        let default = LatticeTopology::default();
+
+        LatticeTopology {
+            neighborhood: match t.neighborhood {
+                Some(NeighborhoodPayload::Moore) => Neighborhood::Moore,
+                Some(NeighborhoodPayload::VonNeumann) => Neighborhood::VonNeumann,
+                None => default.neighborhood,
+            },
+            wrap: t.wrap.unwrap_or(default.wrap),
+        }
+    });
+
+    EntitySpec {
+        size: (payload.width, payload.height),
+        memory_size: payload.memory_size,
+        initial_states: payload
+            .initial_states
+            .into_iter()
+            .map(|s| CognitiveUnitPair {
+                rule: s.rule,
+                state: s.state,
+            })
+            .collect(),
+        topology,
+        resolvers: payload.resolvers,
+        assignment: payload
+            .assignment
+            .map(assignment_strategy)
+            .unwrap_or_default(),
+        species: payload.species.map(|species| SpeciesSpec {
+            resolvers: species.resolvers,
+            layout: match species.layout {
+                Some(SpeciesLayoutPayload::Cyclic) => SpeciesLayout::Cyclic,
+                Some(SpeciesLayoutPayload::Bands) | None => SpeciesLayout::Bands,
+            },
+            inherit: species.inherit.unwrap_or_default(),
+        }),
+        cache_responses: payload.cache_responses.unwrap_or(false),
+    }
+}
+
+fn assignment_strategy(payload: AssignmentPayload) -> AssignmentStrategy {
+    match payload {
+        AssignmentPayload::RoundRobin => AssignmentStrategy::RoundRobin,
+        AssignmentPayload::Weighted => AssignmentStrategy::Weighted,
+        AssignmentPayload::Sticky => AssignmentStrategy::Sticky,
+        AssignmentPayload::Region => AssignmentStrategy::Region,
+        AssignmentPayload::Adaptive => AssignmentStrategy::Adaptive,
+    }
+}
+
+fn assignment_payload(strategy: AssignmentStrategy) -> AssignmentPayload {
+    match strategy {
+        AssignmentStrategy::RoundRobin => AssignmentPayload::RoundRobin,
+        AssignmentStrategy::Weighted => AssignmentPayload::Weighted,
+        AssignmentStrategy::Sticky => AssignmentPayload::Sticky,
+        AssignmentStrategy::Region => AssignmentPayload::Region,
+        AssignmentStrategy::Adaptive => AssignmentPayload::Adaptive,
+    }
+}
+
+pub fn intervention(payload: InteractionPayload, default_author: &str) -> Intervention {
+    Intervention {
+        author: payload.author.unwrap_or_else(|| default_author.to_string()),
+        note: payload.note,
+        actions: payload
+            .actions
+            .into_iter()
+            .map(|action| match action {
+                InteractionAction::SetUnit(a) => Action::SetUnit {
+                    position: (a.x, a.y),
+                    rule: a.rule,
+                    state: a.state,
+                },
+                InteractionAction::Feedback(a) => Action::Feedback {
+                    position: (a.x, a.y),
+                    message: a.message,
+                },
+                InteractionAction::Paint(a) => Action::Paint {
+                    from: (a.from_x, a.from_y),
+                    to: (a.to_x, a.to_y),
+                    rule: a.rule,
+                    state: a.state,
+                },
+                InteractionAction::Broadcast(a) => Action::Broadcast { message: a.message },
+            })
+            .collect(),
+    }
+}
+
+pub fn run_spec(payload: RunPayload) -> RunSpec {
+    RunSpec {
+        max_steps: payload.steps,
+        stop_when: StopConditions {
+            fixed_point: payload.stop_on_fixed_point.unwrap_or(false),
+            unique_states_below: payload.unique_states_below,
+            token_budget: payload.token_budget,
+            cost_budget: payload.cost_budget,
+            max_duration_secs: payload.max_duration_secs,
+        },
+    }
+}
+
+pub fn llm_resolver(payload: ResolverPayload) -> LLMResolver {
+    let mut resolver = LLMResolver::new(
+        Some(payload.name),
+        payload.api_url,
+        payload.api_key.unwrap_or_else(|| "_".to_string()),
+        payload.model_name,
+        payload.provider.map(|provider| match provider {
+            ProviderPayload::Ollama => LLMProvider::Ollama,
+            ProviderPayload::OpenRouter => LLMProvider::OpenRouter,
+        }),
+    );
+
+    resolver.set_pricing(payload.pricing.map(|pricing| ResolverPricing {
+        input_per_million: pricing.input_per_million,
+        output_per_million: pricing.output_per_million,
+    }));
+    resolver.set_capacity(payload.capacity);
+    resolver.set_policy(payload.policy.map(resolver_policy).unwrap_or_default());
+
+    resolver
+}
+
+#[derive(ApiResponse)]
+pub enum CreateEntityResponse {
+    #[oai(status = 201)]
+    Created(Json<CreatedEntity>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 500)]
+    Failed(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+pub enum EntityResponse<T: ToJSON> {
+    #[oai(status = 200)]
+    Ok(Json<T>),
+    #[oai(status = 202)]
+    Accepted(Json<T>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    /// The entity is computing a step, already has an active run or, when
+    /// registering one, already exists.
+    #[oai(status = 409)]
+    Conflict(Json<ConflictBody>),
+    /// The caller's step or token quota is used up.
+    #[oai(status = 429)]
+    TooManyRequests(Json<ErrorBody>),
+    #[oai(status = 500)]
+    Failed(Json<ErrorBody>),
+}
+
+impl<T: ToJSON> EntityResponse<T> {
+    pub fn conflict(error: &str, computing_step: Option<u32>, run: Option<String>) -> Self {
+        Self::Conflict(Json(ConflictBody {
+            error: error.to_string(),
+            computing_step,
+            run,
+        }))
+    }
+}
+
+impl<T: ToJSON> From<RegistryError> for EntityResponse<T> {
+    fn from(err: RegistryError) -> Self {
+        match err {
+            RegistryError::NotFound => Self::NotFound(error_body("entity not found")),
+            RegistryError::Busy(step) => {
+                Self::conflict("entity is computing a step", Some(step), None)
+            }
+            RegistryError::QuotaExceeded(err) => Self::TooManyRequests(error_body(err)),
+            RegistryError::Failed(err) => Self::Failed(error_body(err)),
+        }
+    }
+}
+
+/// Missing steps and archives are 404, invalid requests 400, and anything
+/// else a failure of the server.
+impl<T: ToJSON> From<io::Error> for EntityResponse<T> {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(error_body(err.to_string())),
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => {
+                Self::BadRequest(error_body(err.to_string()))
+            }
+            _ => Self::Failed(error_body(err.to_string())),
+        }
+    }
+}
+
+impl<T: ToJSON> From<RunError> for EntityResponse<T> {
+    fn from(err: RunError) -> Self {
+        match err {
+            RunError::NotFound => Self::NotFound(error_body("run not found")),
+            RunError::EntityNotFound => RegistryError::NotFound.into(),
+            RunError::Invalid(err) => Self::BadRequest(error_body(err)),
+            RunError::AlreadyRunning(run) => {
+                Self::conflict("entity already has an active run", None, Some(run))
+            }
+            RunError::Finished => Self::conflict("run has finished", None, None),
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+pub enum EventsResponse {
+    #[oai(status = 200)]
+    Stream(EventStream<BoxStream<'static, StepEventView>>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+pub enum ExportResponse {
+    #[oai(status = 200)]
+    Bundle(Attachment<Vec<u8>>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    Failed(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+pub enum ImageResponse {
+    #[oai(status = 200, content_type = "image/png")]
+    Png(Binary<Vec<u8>>),
+    #[oai(status = 200, content_type = "image/gif")]
+    Gif(Binary<Vec<u8>>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 500)]
+    Failed(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+pub enum ResolverResponse {
+    #[oai(status = 200)]
+    Ok(Json<ResolverView>),
+    #[oai(status = 201)]
+    Created(Json<ResolverView>),
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorBody>),
+    /// Only admins may change resolvers.
+    #[oai(status = 403)]
+    Forbidden(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+    #[oai(status = 409)]
+    Conflict(Json<ErrorBody>),
+    #[oai(status = 500)]
+    Failed(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+pub enum ResolverListResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<ResolverView>>),
+    #[oai(status = 403)]
+    Forbidden(Json<ErrorBody>),
+    #[oai(status = 500)]
+    Failed(Json<ErrorBody>),
+}
+
+#[derive(ApiResponse)]
+pub enum ResolverHealthResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<ResolverHealthView>>),
+    #[oai(status = 403)]
+    Forbidden(Json<ErrorBody>),
+    #[oai(status = 404)]
+    NotFound(Json<ErrorBody>),
+}
@@ -0,0 +1,163 @@
+use std::{collections::HashMap, sync::Arc};
+
+use dynamical_system::{
+    life::{
+        entity::{Entity, EntityState},
+        events::{step_event_channel, StepEvent},
+        manager::LifeManager,
+    },
+    system::telemetry::StepTelemetry,
+};
+use tokio::sync::{broadcast, Mutex, OwnedMutexGuard, RwLock};
+use tracing::{error, info};
+
+pub type SharedEntity = Arc<Mutex<Entity>>;
+
+#[derive(Debug)]
+pub enum RegistryError {
+    NotFound,
+    /// The entity is computing the given step.
+    Busy(u32),
+    /// The caller's quota does not allow another step.
+    QuotaExceeded(String),
+    Failed(String),
+}
+
+/// Every entity the server has touched, each behind its own lock.
+///
+/// The [`LifeManager`] is only locked to open, create or import entities;
+/// once an entity is here, requests for different entities never contend.
+/// A step is computed on a copy of the entity so the lock is not held while
+/// the resolvers are queried: reads see the previous step with
+/// [`EntityState::ComputingStep`], and mutations are refused until it ends.
+#[derive(Debug)]
+pub struct EntityRegistry {
+    life_manager: RwLock<LifeManager>,
+    entities: RwLock<HashMap<String, SharedEntity>>,
+}
+
+impl EntityRegistry {
+    pub fn new(life_manager: LifeManager) -> Self {
+        Self {
+            life_manager: RwLock::new(life_manager),
+            entities: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn life_manager(&self) -> &RwLock<LifeManager> {
+        &self.life_manager
+    }
+
+    /// Returns the entity, opening it through the [`LifeManager`] the first
+    /// time it is requested.
+    pub async fn get(&self, id: &str) -> Option<SharedEntity> {
+        if let Some(entity) = self.entities.read().await.get(id) {
+            return Some(entity.clone());
+        }
+
+        // Opened without the map lock, so a slow disk or a busy manager
+        // does not hold up requests for other entities.
+        let mut entity = self.life_manager.read().await.open_entity(id)?;
+        entity.set_event_sender(Some(step_event_channel()));
+
+        // Another request may have opened it in the meantime; keep theirs.
+        let entity = self
+            .entities
+            .write()
+            .await
+            .entry(id.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(entity)))
+            .clone();
+
+        Some(entity)
+    }
+
+    pub async fn insert(&self, mut entity: Entity) -> SharedEntity {
+        let id = entity.id().to_string();
+        entity.set_event_sender(Some(step_event_channel()));
+
+        let entity = Arc::new(Mutex::new(entity));
+
+        self.entities.write().await.insert(id, entity.clone());
+
+        entity
+    }
+
+    /// Step progress of the entity, from the next event on.
+    pub async fn subscribe(&self, id: &str) -> Option<broadcast::Receiver<StepEvent>> {
+        let entity = self.get(id).await?;
+        let entity = entity.lock().await;
+
+        entity.event_sender().map(|sender| sender.subscribe())
+    }
+
+    /// Locks the entity for a mutation, failing with [`RegistryError::Busy`]
+    /// while it is computing a step.
+    pub async fn lock_idle(&self, id: &str) -> Result<OwnedMutexGuard<Entity>, RegistryError> {
+        let entity = self.get(id).await.ok_or(RegistryError::NotFound)?;
+        let guard = entity.lock_owned().await;
+
+        match guard.state() {
+            EntityState::ComputingStep(step) => Err(RegistryError::Busy(*step)),
+            EntityState::Idle => Ok(guard),
+        }
+    }
+
+    /// Computes the next step of the entity without holding its lock.
+    pub async fn evolve(&self, id: &str) -> Result<StepTelemetry, RegistryError> {
+        let entity = self.get(id).await.ok_or(RegistryError::NotFound)?;
+
+        let mut working = {
+            let mut guard = entity.lock().await;
+
+            if let EntityState::ComputingStep(step) = guard.state() {
+                return Err(RegistryError::Busy(*step));
+            }
+
+            let step = guard.current_step() + 1;
+
+            guard.set_state(EntityState::ComputingStep(step));
+            guard.clone()
+        };
+
+        info!(
+            entity_id = id,
+            step = working.current_step() + 1,
+            "registry_step_started"
+        );
+
+        // The step is finished, swapped in or reset by a task of its own, so
+        // the entity is not left computing if the caller goes away.
+        let entity_id = id.to_string();
+
+        let task = tokio::spawn(async move {
+            let result = tokio::spawn(async move {
+                let telemetry = working.evolve_async().await;
+                (working, telemetry)
+            })
+            .await;
+
+            let mut guard = entity.lock().await;
+
+            match result {
+                Ok((evolved, Ok(telemetry))) => {
+                    *guard = evolved;
+                    Ok(telemetry)
+                }
+                Ok((_, Err(err))) => {
+                    guard.set_state(EntityState::Idle);
+                    Err(RegistryError::Failed(err.to_string()))
+                }
+                Err(err) => {
+                    error!(entity_id, error = %err, "registry_step_failed");
+
+                    guard.set_state(EntityState::Idle);
+                    Err(RegistryError::Failed(err.to_string()))
+                }
+            }
+        });
+
+        task.await
+            .unwrap_or_else(|err| Err(RegistryError::Failed(err.to_string())))
+    }
+}
@@ -0,0 +1,52 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::registry::EntityRegistry;
+
+/// How often the resolvers file is checked for changes.
+const RESOLVERS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Reloads the resolvers file whenever its modification time changes. A file
+/// that fails to parse is logged and the current pool is kept.
+pub fn watch_resolvers(registry: Arc<EntityRegistry>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let path = registry
+            .life_manager()
+            .read()
+            .await
+            .config()
+            .resolvers_path
+            .clone();
+
+        let modified = || {
+            std::fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .ok()
+        };
+        let mut last_modified = modified();
+
+        info!(resolvers_path = %path.display(), "resolvers_watch_started");
+
+        loop {
+            tokio::time::sleep(RESOLVERS_POLL_INTERVAL).await;
+
+            let current = modified();
+
+            if current.is_none() || current == last_modified {
+                continue;
+            }
+
+            last_modified = current;
+
+            if let Err(err) = registry.life_manager().read().await.reload_resolvers() {
+                warn!(
+                    resolvers_path = %path.display(),
+                    error = %err,
+                    "resolvers_reload_failed"
+                );
+            }
+        }
+    })
+}
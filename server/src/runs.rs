@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use dynamical_system::life::run::{lattice_signature, RunSpec, RunState, RunStatus, StopReason};
+use tokio::sync::{watch, RwLock};
+use tracing::{info, warn};
+
+use crate::{
+    auth::{Auth, User},
+    registry::{EntityRegistry, RegistryError},
+};
+
+/// How long a run waits before retrying when its entity is busy with a step
+/// started by someone else.
+const BUSY_RETRY: Duration = Duration::from_millis(500);
+
+/// Finished and failed runs kept for polling; the oldest are dropped first.
+const MAX_DONE_RUNS: usize = 256;
+
+#[derive(Debug)]
+pub enum RunError {
+    NotFound,
+    EntityNotFound,
+    Invalid(String),
+    /// The entity already has an active run with this id.
+    AlreadyRunning(String),
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunControl {
+    Run,
+    Pause,
+    Cancel,
+}
+
+#[derive(Debug)]
+struct RunHandle {
+    /// Whose quota the run's steps are charged to.
+    user: Arc<User>,
+    status: Mutex<RunStatus>,
+    control: watch::Sender<RunControl>,
+}
+
+impl RunHandle {
+    fn status(&self) -> RunStatus {
+        let mut status = self.status.lock().unwrap();
+
+        if !status.is_done() {
+            status.tick();
+        }
+
+        status.clone()
+    }
+}
+
+/// Drops the oldest finished runs beyond [`MAX_DONE_RUNS`]. Active runs are
+/// always kept.
+fn evict_done_runs(runs: &mut HashMap<String, Arc<RunHandle>>) {
+    let mut done = runs
+        .iter()
+        .filter_map(|(id, run)| {
+            let status = run.status.lock().unwrap();
+
+            status.is_done().then(|| (status.finished_at, id.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    if done.len() <= MAX_DONE_RUNS {
+        return;
+    }
+
+    done.sort();
+
+    for (_, id) in &done[..done.len() - MAX_DONE_RUNS] {
+        runs.remove(id);
+    }
+
+    info!(evicted = done.len() - MAX_DONE_RUNS, "runs_evicted");
+}
+
+/// Background jobs that evolve an entity step by step until a
+/// [`RunSpec`] says to stop. Pausing and cancelling take effect between steps.
+#[derive(Debug)]
+pub struct RunManager {
+    registry: Arc<EntityRegistry>,
+    auth: Arc<Auth>,
+    runs: RwLock<HashMap<String, Arc<RunHandle>>>,
+    next_id: AtomicU64,
+}
+
+impl RunManager {
+    pub fn new(registry: Arc<EntityRegistry>, auth: Arc<Auth>) -> Self {
+        Self {
+            registry,
+            auth,
+            runs: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub async fn start(
+        &self,
+        entity_id: &str,
+        spec: RunSpec,
+        user: Arc<User>,
+    ) -> Result<RunStatus, RunError> {
+        if spec.max_steps == 0 {
+            return Err(RunError::Invalid("max_steps must be positive".to_string()));
+        }
+
+        let entity = self
+            .registry
+            .get(entity_id)
+            .await
+            .ok_or(RunError::EntityNotFound)?;
+
+        let mut runs = self.runs.write().await;
+
+        if let Some(active) = runs.values().find(|run| {
+            let status = run.status.lock().unwrap();
+            status.entity_id == entity_id && !status.is_done()
+        }) {
+            return Err(RunError::AlreadyRunning(
+                active.status.lock().unwrap().id.clone(),
+            ));
+        }
+
+        let id = format!(
+            "run-{}-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let start_step = entity.lock().await.current_step();
+        let status = RunStatus::new(id.clone(), entity_id.to_string(), spec, start_step);
+
+        let (control, control_rx) = watch::channel(RunControl::Run);
+        let handle = Arc::new(RunHandle {
+            user: user.clone(),
+            status: Mutex::new(status.clone()),
+            control,
+        });
+
+        runs.insert(id.clone(), handle.clone());
+        evict_done_runs(&mut runs);
+
+        info!(run_id = %id, entity_id, start_step, user = %user.name, "run_started");
+
+        tokio::spawn(drive(
+            self.registry.clone(),
+            self.auth.clone(),
+            handle,
+            control_rx,
+        ));
+
+        Ok(status)
+    }
+
+    pub async fn get(&self, id: &str) -> Option<RunStatus> {
+        let runs = self.runs.read().await;
+
+        runs.get(id).map(|run| run.status())
+    }
+
+    /// Runs of an entity, oldest first.
+    pub async fn list(&self, entity_id: &str) -> Vec<RunStatus> {
+        let mut runs = self
+            .runs
+            .read()
+            .await
+            .values()
+            .map(|run| run.status())
+            .filter(|status| status.entity_id == entity_id)
+            .collect::<Vec<_>>();
+
+        runs.sort_by_key(|status| status.started_at);
+
+        runs
+    }
+
+    pub async fn pause(&self, id: &str) -> Result<RunStatus, RunError> {
+        self.control(id, RunControl::Pause).await
+    }
+
+    pub async fn resume(&self, id: &str) -> Result<RunStatus, RunError> {
+        self.control(id, RunControl::Run).await
+    }
+
+    pub async fn cancel(&self, id: &str) -> Result<RunStatus, RunError> {
+        self.control(id, RunControl::Cancel).await
+    }
+
+    async fn control(&self, id: &str, control: RunControl) -> Result<RunStatus, RunError> {
+        let run = self
+            .runs
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or(RunError::NotFound)?;
+
+        let mut status = run.status.lock().unwrap();
+
+        if status.is_done() {
+            return Err(RunError::Finished);
+        }
+
+        match control {
+            RunControl::Pause => status.state = RunState::Paused,
+            RunControl::Run => status.state = RunState::Running,
+            RunControl::Cancel => {}
+        }
+
+        run.control.send_replace(control);
+
+        info!(run_id = id, control = ?control, "run_control_changed");
+
+        Ok(status.clone())
+    }
+}
+
+async fn drive(
+    registry: Arc<EntityRegistry>,
+    auth: Arc<Auth>,
+    run: Arc<RunHandle>,
+    mut control: watch::Receiver<RunControl>,
+) {
+    let (run_id, entity_id) = {
+        let status = run.status.lock().unwrap();
+        (status.id.clone(), status.entity_id.clone())
+    };
+
+    let outcome = loop {
+        let current = *control.borrow_and_update();
+
+        match current {
+            RunControl::Cancel => break Ok(StopReason::Cancelled),
+            RunControl::Pause => {
+                run.status.lock().unwrap().stop_clock();
+
+                if control.changed().await.is_err() {
+                    break Ok(StopReason::Cancelled);
+                }
+
+                continue;
+            }
+            RunControl::Run => run.status.lock().unwrap().start_clock(),
+        }
+
+        let Some(entity) = registry.get(&entity_id).await else {
+            break Err("entity not found".to_string());
+        };
+
+        let before = lattice_signature(entity.lock().await.loaded_space());
+
+        match auth.evolve(&registry, &run.user, &entity_id).await {
+            Ok(telemetry) => {
+                let after = lattice_signature(entity.lock().await.loaded_space());
+                let stop_reason = run
+                    .status
+                    .lock()
+                    .unwrap()
+                    .record_step(telemetry, before == after);
+
+                if let Some(stop_reason) = stop_reason {
+                    break Ok(stop_reason);
+                }
+            }
+            Err(RegistryError::Busy(step)) => {
+                warn!(run_id = %run_id, entity_id = %entity_id, step, "run_waiting_for_entity");
+
+                tokio::select! {
+                    _ = tokio::time::sleep(BUSY_RETRY) => {}
+                    _ = control.changed() => {}
+                }
+            }
+            Err(RegistryError::QuotaExceeded(_)) => break Ok(StopReason::QuotaExceeded),
+            Err(RegistryError::NotFound) => break Err("entity not found".to_string()),
+            Err(RegistryError::Failed(err)) => break Err(err),
+        }
+    };
+
+    let mut status = run.status.lock().unwrap();
+
+    match outcome {
+        Ok(stop_reason) => status.finish(stop_reason),
+        Err(err) => status.fail(err),
+    }
+
+    info!(
+        run_id = %run_id,
+        entity_id = %entity_id,
+        state = ?status.state,
+        stop_reason = ?status.stop_reason,
+        steps_completed = status.steps_completed,
+        tokens_used = status.tokens_used,
+        elapsed_ms = status.elapsed_ms,
+        "run_finished"
+    );
+}